@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+
+use crate::archive_tree::ArchiveTree;
+use crate::cursor::{Cursor, Entry, Settings, Sort};
+use crate::matcher::Matcher;
+
+/// What an archive format supplies besides its listing, which is read into
+/// an `ArchiveTree` once when the archive is opened.
+pub trait Members {
+    /// The content of the file member listed under `path`.
+    fn read(&mut self, path: &Path) -> Result<Vec<u8>>;
+}
+
+/// Browses an archive below a virtual directory, the path of the archive
+/// itself. Tar and zip archives only differ in how they are listed and how
+/// members are read, see `TarMembers` and `ZipMembers`.
+pub struct ArchiveCursor {
+    settings: Settings,
+    start_cwd: PathBuf,
+    tree: ArchiveTree,
+    members: Box<dyn Members>,
+    paths: HashMap<PathBuf, PathBuf>,
+    filters: HashMap<PathBuf, String>,
+    selected: PathBuf,
+}
+
+impl ArchiveCursor {
+    /// Browses the archive listed in `tree` below `root`, which differs from
+    /// the file the members are read from for an archive nested in another
+    /// archive.
+    pub fn new(root: &Path, tree: ArchiveTree, members: Box<dyn Members>) -> Self {
+        let mut cursor = Self {
+            settings: Settings::new(),
+            start_cwd: root.to_path_buf(),
+            tree,
+            members,
+            paths: HashMap::new(),
+            filters: HashMap::new(),
+            selected: PathBuf::new(),
+        };
+        cursor.selected = cursor.first(root);
+        cursor
+    }
+
+    /// The first entry listed in `dir`, or `dir/..` when it is empty.
+    fn first(&self, dir: &Path) -> PathBuf {
+        match self.view(dir).into_iter().next() {
+            Some(p) => p,
+            None => dir.join(".."),
+        }
+    }
+
+    fn view(&self, dir: &Path) -> Vec<PathBuf> {
+        let entries = self.tree.get(dir).map(|e| e.as_slice());
+        let filter = self.filters.get(dir).map(String::as_str);
+        self.settings.view(entries.unwrap_or_default(), filter)
+    }
+}
+
+impl Cursor for ArchiveCursor {
+    fn init(&mut self, cwd: &Path) -> Result<()> {
+        self.start_cwd = cwd.to_path_buf();
+        self.paths = HashMap::new();
+        self.selected = self.first(cwd);
+        Ok(())
+    }
+
+    fn read_file_content(&mut self, path: &Path) -> Result<Vec<u8>> {
+        self.members.read(path)
+    }
+
+    fn move_down(&mut self, n: i32) -> Result<()> {
+        let siblings = self.siblings(self.current_dir())?;
+        let pos = self.pos()?;
+        if pos < siblings.len() as i32 - n {
+            self.selected = siblings[(pos + n) as usize].clone();
+        } else {
+            self.selected = siblings.last().unwrap_or(&self.selected).clone()
+        }
+        Ok(())
+    }
+
+    fn move_up(&mut self, n: i32) -> Result<()> {
+        let siblings = self.siblings(self.current_dir())?;
+        let pos = self.pos()?;
+        if pos >= n {
+            self.selected = siblings[(pos - n) as usize].clone();
+        } else {
+            self.selected = siblings.first().unwrap_or(&self.selected).clone()
+        }
+        Ok(())
+    }
+
+    fn move_in(&mut self) -> Result<()> {
+        if self
+            .selected()
+            .to_str()
+            .map(|s| s.ends_with('/'))
+            .unwrap_or(false)
+            && self.selected() != self.current_dir()
+            && !self.selected().ends_with("..")
+        {
+            self.paths.insert(self.current_dir(), self.selected());
+            let selected = if let Some(p) = self.paths.get(&self.selected()) {
+                p.clone()
+            } else {
+                match self.siblings(self.selected())?.first() {
+                    Some(p) => p.clone(),
+                    None => self.selected().join(PathBuf::from("..")),
+                }
+            };
+            self.selected = selected
+        }
+        Ok(())
+    }
+
+    fn move_out(&mut self) -> Result<()> {
+        if self.current_dir().parent().is_some() {
+            self.paths.insert(self.current_dir(), self.selected());
+            self.selected = match self.paths.get(&self.parent()) {
+                Some(p) => p.clone(),
+                None => self.current_dir(),
+            };
+        }
+        Ok(())
+    }
+
+    fn move_bottom(&mut self) -> Result<()> {
+        let siblings = self.siblings(self.current_dir())?;
+        if let Some(p) = siblings.last() {
+            self.selected = p.clone();
+        }
+        Ok(())
+    }
+
+    fn move_top(&mut self) -> Result<()> {
+        let siblings = self.siblings(self.current_dir())?;
+        if let Some(p) = siblings.first() {
+            self.selected = p.clone();
+        }
+        Ok(())
+    }
+
+    fn toggle_hidden_files(&mut self) -> Result<()> {
+        self.settings.hide = !self.settings.hide;
+        Ok(())
+    }
+
+    fn hides_hidden_files(&self) -> bool {
+        self.settings.hide
+    }
+
+    fn toggle_case_sensitivity(&mut self) -> Result<()> {
+        self.settings.casing = !self.settings.casing;
+        Ok(())
+    }
+
+    fn sort(&self) -> Sort {
+        self.settings.sort.clone()
+    }
+
+    fn sort_dir(&mut self) -> Result<()> {
+        self.settings.sort = Sort::Dir;
+        Ok(())
+    }
+
+    fn sort_name(&mut self) -> Result<()> {
+        self.settings.sort = Sort::Name;
+        Ok(())
+    }
+
+    fn sort_size(&mut self) -> Result<()> {
+        self.settings.sort = Sort::Size;
+        Ok(())
+    }
+
+    fn sort_time(&mut self) -> Result<()> {
+        self.settings.sort = Sort::Time;
+        Ok(())
+    }
+
+    fn search(&mut self, pattern: &str) -> Result<()> {
+        let matches = self.matching_siblings(pattern)?;
+        if let Some(path) = matches.first() {
+            self.selected = path.into()
+        }
+        Ok(())
+    }
+
+    fn matching_siblings(&mut self, pattern: &str) -> Result<Vec<PathBuf>> {
+        let matcher = match Matcher::new(pattern) {
+            Ok(matcher) => matcher,
+            Err(_) => return Ok(Vec::new()),
+        };
+        let siblings = self.siblings(self.current_dir())?;
+        Ok(siblings
+            .into_iter()
+            .filter(|sibling| matcher.is_match(sibling))
+            .collect())
+    }
+
+    fn filter(&self) -> Option<String> {
+        self.filters.get(&self.current_dir()).cloned()
+    }
+
+    fn set_filter(&mut self, pattern: &str) -> Result<()> {
+        let dir = self.current_dir();
+        if pattern.is_empty() {
+            self.filters.remove(&dir);
+        } else {
+            self.filters.insert(dir.clone(), pattern.to_string());
+        }
+        let siblings = self.siblings(dir.clone())?;
+        if !siblings.contains(&self.selected) {
+            self.selected = match siblings.first() {
+                Some(p) => p.clone(),
+                None => dir.join(".."),
+            };
+        }
+        Ok(())
+    }
+
+    fn select(&mut self, path: &Path) -> Result<()> {
+        self.selected = path.to_path_buf();
+        Ok(())
+    }
+
+    fn selected(&self) -> PathBuf {
+        self.selected.clone()
+    }
+
+    fn current_dir(&self) -> PathBuf {
+        PathBuf::from(&self.selected().parent().unwrap_or("".as_ref()))
+    }
+
+    fn start_dir(&self) -> PathBuf {
+        self.start_cwd.clone()
+    }
+
+    fn parent(&self) -> PathBuf {
+        self.current_dir()
+            .parent()
+            .unwrap_or(&PathBuf::from(""))
+            .to_path_buf()
+    }
+
+    fn current_len(&mut self) -> Result<usize> {
+        Ok(self.view(&self.current_dir()).len())
+    }
+
+    fn current_window(&mut self, range: Range<usize>) -> Result<Vec<PathBuf>> {
+        let mut paths = self.view(&self.current_dir());
+        paths.truncate(range.end);
+        Ok(paths.split_off(range.start.min(paths.len())))
+    }
+
+    fn siblings(&mut self, path: PathBuf) -> Result<Vec<PathBuf>> {
+        Ok(self.view(&path))
+    }
+
+    fn entries(&mut self, dir: &Path) -> Result<Vec<Entry>> {
+        Ok(self.tree.get(dir).cloned().unwrap_or_default())
+    }
+
+    fn is_unsafe(&self, path: &Path) -> bool {
+        self.tree.is_unsafe(path)
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn invalidate(&mut self, _dir: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn pos(&mut self) -> Result<i32> {
+        let pos = self
+            .siblings(self.current_dir())?
+            .iter()
+            .position(|p| self.selected.file_name() == p.file_name())
+            .unwrap_or(0) as i32;
+        Ok(pos)
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::cursor::Entry;
+
+/// The directory structure of an archive, keyed by the virtual path of each
+/// directory below the archive root. Directory paths end in `/`.
+#[derive(Default)]
+pub struct ArchiveTree {
+    children: HashMap<PathBuf, Vec<Entry>>,
+    dirs: HashSet<PathBuf>,
+    unsafe_paths: HashSet<PathBuf>,
+}
+
+impl ArchiveTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, dir: &Path) -> Option<&Vec<Entry>> {
+        self.children.get(dir)
+    }
+
+    /// Whether the member listed under `path` was named with an absolute
+    /// path or one containing `..`, which would escape the directory it is
+    /// extracted to.
+    pub fn is_unsafe(&self, path: &Path) -> bool {
+        self.unsafe_paths.contains(path)
+    }
+
+    /// Adds a member named `name` below `root` and returns the path it is
+    /// listed under. Archives don't have to list directories, so a node is
+    /// created for every ancestor that hasn't been seen yet; an explicit
+    /// entry for a directory that was synthesised earlier replaces it.
+    pub fn insert(&mut self, root: &Path, name: &Path, entry: Entry) -> Option<PathBuf> {
+        let components = normalise(name);
+        let traversal = name
+            .components()
+            .any(|c| matches!(c, Component::RootDir | Component::ParentDir));
+        let mut node = root.to_path_buf();
+        for (i, component) in components.iter().enumerate() {
+            let parent = node.clone();
+            node.push(component);
+            let last = i + 1 == components.len();
+
+            if last && !entry.is_dir {
+                let path = node.clone();
+                self.children.entry(parent).or_default().push(Entry {
+                    path: path.clone(),
+                    ..entry
+                });
+                if traversal {
+                    self.unsafe_paths.insert(path.clone());
+                }
+                return Some(path);
+            }
+
+            let mut dir = node.clone().into_os_string();
+            dir.push("/");
+            let dir = PathBuf::from(dir);
+            let siblings = self.children.entry(parent).or_default();
+            if self.dirs.insert(node.clone()) {
+                siblings.push(Entry {
+                    path: dir.clone(),
+                    size: 0,
+                    mtime: UNIX_EPOCH,
+                    mode: 0o755,
+                    is_dir: true,
+                    link: None,
+                });
+            }
+            if last {
+                if let Some(existing) = siblings.iter_mut().find(|e| e.path == dir) {
+                    *existing = Entry {
+                        path: dir.clone(),
+                        ..entry
+                    };
+                }
+                if traversal {
+                    self.unsafe_paths.insert(dir.clone());
+                }
+                return Some(dir);
+            }
+        }
+        None
+    }
+}
+
+/// Splits a member name into the components it has below the archive root.
+/// `./` prefixes and leading `/` are dropped and `..` is resolved, so every
+/// member ends up reachable from the root.
+pub fn normalise(name: &Path) -> Vec<PathBuf> {
+    let mut components = Vec::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(c) => components.push(PathBuf::from(c)),
+            Component::ParentDir => {
+                components.pop();
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => (),
+        }
+    }
+    components
+}
@@ -0,0 +1,451 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::engine::OpType;
+
+/// What a command takes after its name, which decides how it is completed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Arg {
+    None,
+    Count,
+    Text,
+    Path,
+    Word(&'static [&'static str]),
+}
+
+/// A command of the `:` command line. `op` builds the operation from the
+/// argument, which is empty when none was given, and returns `None` when
+/// the argument doesn't fit.
+pub struct Command {
+    pub name: &'static str,
+    pub arg: Arg,
+    pub op: fn(&str) -> Option<OpType>,
+}
+
+/// Every command, key bound operations included. New commands only need
+/// an entry here.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "q",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opq),
+    },
+    Command {
+        name: "quit",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opq),
+    },
+    Command {
+        name: "cd",
+        arg: Arg::Path,
+        op: |arg| Some(OpType::Opcd(arg.to_string())),
+    },
+    Command {
+        name: "up",
+        arg: Arg::Count,
+        op: |arg| count(arg).map(OpType::Opk),
+    },
+    Command {
+        name: "down",
+        arg: Arg::Count,
+        op: |arg| count(arg).map(OpType::Opj),
+    },
+    Command {
+        name: "back",
+        arg: Arg::None,
+        op: |_| Some(OpType::Oph),
+    },
+    Command {
+        name: "open",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opl),
+    },
+    Command {
+        name: "top",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opgg),
+    },
+    Command {
+        name: "bottom",
+        arg: Arg::None,
+        op: |_| Some(OpType::OpG),
+    },
+    Command {
+        name: "screentop",
+        arg: Arg::None,
+        op: |_| Some(OpType::OpH),
+    },
+    Command {
+        name: "screenmiddle",
+        arg: Arg::None,
+        op: |_| Some(OpType::OpM),
+    },
+    Command {
+        name: "screenbottom",
+        arg: Arg::None,
+        op: |_| Some(OpType::OpL),
+    },
+    Command {
+        name: "halfdown",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opctrld),
+    },
+    Command {
+        name: "halfup",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opctrlu),
+    },
+    Command {
+        name: "pagedown",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opctrlf),
+    },
+    Command {
+        name: "pageup",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opctrlb),
+    },
+    Command {
+        name: "refresh",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opctrll),
+    },
+    Command {
+        name: "hidden",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opdot),
+    },
+    Command {
+        name: "casing",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opcasing),
+    },
+    Command {
+        name: "set",
+        arg: Arg::Word(&["hidden", "nohidden", "hidden!"]),
+        op: |arg| match arg {
+            "hidden" => Some(OpType::Opsethidden(true)),
+            "nohidden" => Some(OpType::Opsethidden(false)),
+            "hidden!" => Some(OpType::Opdot),
+            _ => None,
+        },
+    },
+    Command {
+        name: "sort",
+        arg: Arg::Word(&["dir", "name", "size", "time"]),
+        op: |arg| match arg {
+            "dir" => Some(OpType::Opsortdir),
+            "name" => Some(OpType::Opsortname),
+            "size" => Some(OpType::Opsortsize),
+            "time" => Some(OpType::Opsorttime),
+            _ => None,
+        },
+    },
+    Command {
+        name: "search",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opslash),
+    },
+    Command {
+        name: "command",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opcolon),
+    },
+    Command {
+        name: "next",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opn),
+    },
+    Command {
+        name: "prev",
+        arg: Arg::None,
+        op: |_| Some(OpType::OpN),
+    },
+    Command {
+        name: "filter",
+        arg: Arg::Text,
+        op: |arg| Some(OpType::Opfilter(arg.to_string())),
+    },
+    Command {
+        name: "page",
+        arg: Arg::None,
+        op: |_| Some(OpType::Oppage),
+    },
+    Command {
+        name: "edit",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opedit),
+    },
+    Command {
+        name: "mark",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opmark),
+    },
+    Command {
+        name: "markall",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opmarkall),
+    },
+    Command {
+        name: "invertmarks",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opinvertmarks),
+    },
+    Command {
+        name: "clearmarks",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opclearmarks),
+    },
+    Command {
+        name: "yank",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opyank),
+    },
+    Command {
+        name: "cut",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opcut),
+    },
+    Command {
+        name: "paste",
+        arg: Arg::None,
+        op: |_| Some(OpType::Oppaste),
+    },
+    Command {
+        name: "trash",
+        arg: Arg::None,
+        op: |_| Some(OpType::Optrash),
+    },
+    Command {
+        name: "delete",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opdelete),
+    },
+    Command {
+        name: "trashview",
+        arg: Arg::None,
+        op: |_| Some(OpType::Optrashview),
+    },
+    Command {
+        name: "restore",
+        arg: Arg::None,
+        op: |_| Some(OpType::Oprestore),
+    },
+    Command {
+        name: "rename",
+        arg: Arg::Text,
+        op: |arg| Some(OpType::Oprename(name(arg))),
+    },
+    Command {
+        name: "bulkrename",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opbulkrename),
+    },
+    Command {
+        name: "touch",
+        arg: Arg::Path,
+        op: |arg| Some(OpType::Opnewfile(name(arg))),
+    },
+    Command {
+        name: "mkdir",
+        arg: Arg::Path,
+        op: |arg| Some(OpType::Opnewdir(name(arg))),
+    },
+    Command {
+        name: "symlink",
+        arg: Arg::Path,
+        op: |arg| Some(OpType::Opsymlink(name(arg))),
+    },
+    Command {
+        name: "hardlink",
+        arg: Arg::Path,
+        op: |arg| Some(OpType::Ophardlink(name(arg))),
+    },
+    Command {
+        name: "extract",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opextract),
+    },
+    Command {
+        name: "compress",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opcompress),
+    },
+    Command {
+        name: "shell",
+        arg: Arg::Text,
+        op: |arg| match arg {
+            "" => Some(OpType::Opbang),
+            command => Some(OpType::Opshell(command.to_string())),
+        },
+    },
+    Command {
+        name: "status",
+        arg: Arg::None,
+        op: |_| Some(OpType::Opquestion),
+    },
+];
+
+/// Turns a command line, without the `:`, into the operation it names.
+/// `g/term` is short for `filter term`.
+pub fn parse(line: &str) -> anyhow::Result<OpType> {
+    let line = line.trim();
+    if let Some(term) = line.strip_prefix("g/") {
+        return Ok(OpType::Opfilter(term.to_string()));
+    }
+    let (name, arg) = split(line);
+    let command = COMMANDS
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| anyhow::anyhow!("unknown command: {}", name))?;
+    if command.arg == Arg::None && !arg.is_empty() {
+        anyhow::bail!("{} takes no argument", name);
+    }
+    (command.op)(arg).ok_or_else(|| anyhow::anyhow!("{}: invalid argument {}", name, arg))
+}
+
+/// Completes the command name, or the argument of commands that take a
+/// word or a path, as far as all candidates agree. Paths are relative to
+/// `dir`. Returns the completed line and the candidates, when there is more
+/// than one.
+pub fn complete(line: &str, dir: &Path) -> (String, Vec<String>) {
+    let (name, arg) = split(line);
+    if !line.trim_start().contains(' ') {
+        let names: Vec<&str> = COMMANDS
+            .iter()
+            .map(|c| c.name)
+            .filter(|n| n.starts_with(name))
+            .collect();
+        return match names.as_slice() {
+            [only] => {
+                let takes_arg = COMMANDS
+                    .iter()
+                    .any(|c| c.name == *only && c.arg != Arg::None);
+                let space = if takes_arg { " " } else { "" };
+                (format!("{}{}", only, space), Vec::new())
+            }
+            names => (
+                common_prefix(names).unwrap_or(name).to_string(),
+                names.iter().map(|n| n.to_string()).collect(),
+            ),
+        };
+    }
+
+    let candidates = match COMMANDS.iter().find(|c| c.name == name).map(|c| c.arg) {
+        Some(Arg::Word(words)) => words
+            .iter()
+            .filter(|w| w.starts_with(arg))
+            .map(|w| w.to_string())
+            .collect(),
+        Some(Arg::Path) => paths(arg, dir),
+        _ => Vec::new(),
+    };
+    let completed: Vec<&str> = candidates.iter().map(|c| c.as_str()).collect();
+    let arg = common_prefix(&completed).unwrap_or(arg);
+    let line = format!("{} {}", name, arg);
+    match candidates.as_slice() {
+        [_] => (line, Vec::new()),
+        _ => {
+            // only the names, the directory part is the same for all
+            let names = candidates
+                .iter()
+                .map(|c| {
+                    let start = c.trim_end_matches('/').rfind('/').map_or(0, |i| i + 1);
+                    c[start..].to_string()
+                })
+                .collect();
+            (line, names)
+        }
+    }
+}
+
+/// Resolves the path argument of `:cd` and the like against `dir`. `~` is
+/// the home directory and no argument at all means home as well.
+pub fn resolve(arg: &str, dir: &Path) -> PathBuf {
+    let home = || {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+    };
+    match arg {
+        "" | "~" => home(),
+        arg => match arg.strip_prefix("~/") {
+            Some(rest) => home().join(rest),
+            None => dir.join(arg),
+        },
+    }
+}
+
+/// The entries whose path starts with `arg`, directories with a trailing
+/// `/`. Hidden entries only show up once `arg` names them with a dot.
+fn paths(arg: &str, dir: &Path) -> Vec<String> {
+    let (base, prefix) = match arg.rfind('/') {
+        Some(i) => arg.split_at(i + 1),
+        None => ("", arg),
+    };
+    let base_dir = match base {
+        "" => dir.to_path_buf(),
+        base => resolve(base, dir),
+    };
+    let entries = match fs::read_dir(base_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut paths: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if e.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", base, name, slash))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// The longest prefix all of `words` share, `None` when there are none.
+fn common_prefix<'a>(words: &[&'a str]) -> Option<&'a str> {
+    let (first, rest) = words.split_first()?;
+    let mut len = first.len();
+    for word in rest {
+        len = first
+            .char_indices()
+            .zip(word.chars())
+            .take_while(|((_, a), b)| a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+    Some(&first[..len])
+}
+
+/// Splits a command line into the command name and its argument.
+fn split(line: &str) -> (&str, &str) {
+    let line = line.trim_start();
+    match line.split_once(' ') {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (line, ""),
+    }
+}
+
+/// A repeat count, 1 when none is given.
+fn count(arg: &str) -> Option<i32> {
+    match arg {
+        "" => Some(1),
+        arg => arg.parse().ok().filter(|n| *n > 0),
+    }
+}
+
+/// The name argument of commands that otherwise ask for it.
+fn name(arg: &str) -> Option<String> {
+    match arg {
+        "" => None,
+        arg => Some(arg.to_string()),
+    }
+}
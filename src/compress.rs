@@ -0,0 +1,163 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::compression::Compression;
+use crate::date;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Tar(Compression),
+    Zip,
+}
+
+impl Format {
+    /// Infers the archive format from the name of the archive to create.
+    pub fn from_name(name: &Path) -> Option<Format> {
+        let name = name.file_name()?.to_string_lossy().to_lowercase();
+        let formats = [
+            (".tar", Format::Tar(Compression::None)),
+            (".tar.gz", Format::Tar(Compression::Gzip)),
+            (".tgz", Format::Tar(Compression::Gzip)),
+            (".tar.bz2", Format::Tar(Compression::Bzip2)),
+            (".tbz2", Format::Tar(Compression::Bzip2)),
+            (".tar.xz", Format::Tar(Compression::Xz)),
+            (".txz", Format::Tar(Compression::Xz)),
+            (".tar.zst", Format::Tar(Compression::Zstd)),
+            (".tzst", Format::Tar(Compression::Zstd)),
+            (".zip", Format::Zip),
+        ];
+        formats
+            .iter()
+            .find(|(ext, _)| name.ends_with(ext))
+            .map(|(_, format)| *format)
+    }
+}
+
+/// Writes `paths` and everything below them to the archive `target`. Each
+/// path is stored under its file name. `progress` is called with the number
+/// of entries written so far, the total and the entry about to be written.
+pub fn compress(
+    paths: &[PathBuf],
+    target: &Path,
+    mut progress: impl FnMut(usize, usize, &Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let format = Format::from_name(target)
+        .ok_or_else(|| anyhow::anyhow!("unknown archive format: {}", target.display()))?;
+
+    let mut entries = Vec::new();
+    for path in paths {
+        let name = PathBuf::from(path.file_name().unwrap_or_default());
+        walk(path, &name, target, &mut entries)?;
+    }
+
+    // built next to the target and moved over it once complete, so a failure
+    // leaves an existing file as it was
+    let dir = match target.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let mut prefix = OsString::from(".");
+    prefix.push(target.file_name().unwrap_or_default());
+    let temp = tempfile::Builder::new()
+        .prefix(&prefix)
+        .permissions(fs::Permissions::from_mode(0o666))
+        .tempfile_in(dir)
+        .with_context(|| format!("can't create {}", target.display()))?;
+    let file = temp.as_file();
+    match format {
+        Format::Tar(compression) => {
+            let mut builder = tar::Builder::new(compression.encoder(file)?);
+            // links are stored as links instead of copies of their target
+            builder.follow_symlinks(false);
+            for (i, (path, name)) in entries.iter().enumerate() {
+                progress(i, entries.len(), name)?;
+                builder
+                    .append_path_with_name(path, name)
+                    .with_context(|| format!("can't add {}", path.display()))?;
+            }
+            builder.into_inner()?.finish()?;
+        }
+        Format::Zip => {
+            let mut zip = ZipWriter::new(file);
+            for (i, (path, name)) in entries.iter().enumerate() {
+                progress(i, entries.len(), name)?;
+                let metadata = fs::symlink_metadata(path)?;
+                let options = FileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .unix_permissions(metadata.permissions().mode() & 0o777)
+                    .last_modified_time(zip_time(metadata.modified()?))
+                    .large_file(metadata.len() >= 1 << 32);
+                let name = name.to_string_lossy();
+                if metadata.is_dir() {
+                    zip.add_directory(name, options)?;
+                } else if metadata.is_symlink() {
+                    let link = fs::read_link(path)?;
+                    zip.add_symlink(name, link.to_string_lossy(), options)?;
+                } else {
+                    zip.start_file(name, options)?;
+                    io::copy(&mut File::open(path)?, &mut zip)
+                        .with_context(|| format!("can't add {}", path.display()))?;
+                }
+            }
+            zip.finish()?;
+        }
+    }
+    temp.persist(target)
+        .with_context(|| format!("can't create {}", target.display()))?;
+    progress(entries.len(), entries.len(), target)
+}
+
+/// Collects `path` and, for directories, everything below it, skipping the
+/// archive being written. Symlinked directories aren't descended into.
+fn walk(
+    path: &Path,
+    name: &Path,
+    target: &Path,
+    entries: &mut Vec<(PathBuf, PathBuf)>,
+) -> anyhow::Result<()> {
+    if path == target {
+        return Ok(());
+    }
+    entries.push((path.to_path_buf(), name.to_path_buf()));
+    if fs::symlink_metadata(path)?.is_dir() {
+        let mut children = fs::read_dir(path)?
+            .map(|e| e.map(|e| e.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        children.sort();
+        for child in children {
+            walk(&path.join(&child), &name.join(&child), target, entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts `time` to a zip timestamp, which has no time zone and is read
+/// as local time by other tools. Zip can't store times before 1980, those
+/// are clamped.
+fn zip_time(time: SystemTime) -> zip::DateTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let secs = secs + date::local_offset(secs);
+    let (year, month, day) = date::civil_from_days(secs.div_euclid(86400));
+    let tod = secs.rem_euclid(86400);
+    zip::DateTime::from_date_and_time(
+        year.clamp(1980, 2107) as u16,
+        month as u8,
+        day as u8,
+        (tod / 3600) as u8,
+        (tod % 3600 / 60) as u8,
+        (tod % 60) as u8,
+    )
+    .unwrap_or_default()
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+use crate::command;
+use crate::engine::OpType;
+use crate::keymap::{self, Key};
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Open,
+    Page,
+    Edit,
+}
+
+pub struct Rule {
+    pub actions: Vec<Action>,
+    pub ext: Vec<String>,
+    pub glob: GlobSet,
+    pub mime: Vec<String>,
+    pub cmd: String,
+}
+
+impl Rule {
+    fn matches(&self, action: Action, name: &Path) -> bool {
+        if !self.actions.contains(&action) {
+            return false;
+        }
+        let ext = name
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if self.ext.iter().any(|e| e.to_lowercase() == ext) {
+            return true;
+        }
+        if let Some(file_name) = name.file_name() {
+            if self.glob.is_match(file_name) {
+                return true;
+            }
+        }
+        let guesses = mime_guess::from_path(name);
+        self.mime.iter().any(|pattern| {
+            guesses.iter().any(|mime| match pattern.strip_suffix("/*") {
+                Some(top) => mime.type_() == top,
+                None => mime.essence_str() == pattern,
+            })
+        })
+    }
+}
+
+pub struct Config {
+    pub editor: String,
+    pub pager: String,
+    pub shell: String,
+    pub opener: String,
+    pub scrolloff: usize,
+    // compressed files larger than this many bytes are piped to the pager
+    // instead of being decompressed to a temp file first
+    pub stream_above: Option<u64>,
+    pub rules: Vec<Rule>,
+    // bindings from the `[keys]` table, `None` unbinds
+    pub keys: Vec<(Vec<Key>, Option<OpType>)>,
+    // milliseconds to wait for the rest of an ambiguous key sequence
+    pub timeout: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    /// Built-in defaults, overridden by `$VISUAL`/`$EDITOR`, `$PAGER` and
+    /// `$SHELL` when they are set.
+    pub fn new() -> Config {
+        Config {
+            editor: env_or(&["VISUAL", "EDITOR"], "vim"),
+            pager: env_or(&["PAGER"], "less"),
+            shell: env_or(&["SHELL"], "bash"),
+            opener: String::from("bat"),
+            scrolloff: 5,
+            stream_above: None,
+            rules: Vec::new(),
+            keys: Vec::new(),
+            timeout: 1000,
+        }
+    }
+
+    /// Loads `$XDG_CONFIG_HOME/noicer/config`, falling back to the defaults
+    /// when the file doesn't exist.
+    pub fn load() -> anyhow::Result<Config> {
+        let mut config = Config::new();
+        let path = match config_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(config),
+        };
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let file: ConfigFile = toml::from_str(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+
+        if let Some(editor) = file.editor {
+            config.editor = editor;
+        }
+        if let Some(pager) = file.pager {
+            config.pager = pager;
+        }
+        if let Some(shell) = file.shell {
+            config.shell = shell;
+        }
+        if let Some(opener) = file.opener {
+            config.opener = opener;
+        }
+        if let Some(scrolloff) = file.scrolloff {
+            config.scrolloff = scrolloff;
+        }
+        if file.stream_above.is_some() {
+            config.stream_above = file.stream_above;
+        }
+        if let Some(timeout) = file.timeout {
+            config.timeout = timeout;
+        }
+        for (keys, action) in file.keys {
+            config.keys.push(
+                binding(&keys, &action).with_context(|| {
+                    format!("invalid key binding {} in {}", keys, path.display())
+                })?,
+            );
+        }
+        for rule in file.rules {
+            config.rules.push(
+                rule.compile()
+                    .with_context(|| format!("invalid rule in {}", path.display()))?,
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// Builds the command line for running `action` on `target`. Rules are
+    /// matched against `name`, which differs from `target` when the file has
+    /// been copied out of an archive into a temp file.
+    pub fn command(
+        &self,
+        action: Action,
+        name: &Path,
+        target: &Path,
+    ) -> anyhow::Result<Vec<String>> {
+        expand(self.template(action, &[name]), &[target])
+    }
+
+    /// Builds the command line for running `action` on all of `targets` at
+    /// once. A rule is only used when it matches every target.
+    pub fn command_many(&self, action: Action, targets: &[PathBuf]) -> anyhow::Result<Vec<String>> {
+        let targets: Vec<&Path> = targets.iter().map(|t| t.as_path()).collect();
+        expand(self.template(action, &targets), &targets)
+    }
+
+    /// Whether a rule is configured for running `action` on `name`.
+    pub fn has_rule(&self, action: Action, name: &Path) -> bool {
+        self.rules.iter().any(|r| r.matches(action, name))
+    }
+
+    fn template(&self, action: Action, names: &[&Path]) -> &str {
+        let rule = self
+            .rules
+            .iter()
+            .find(|r| names.iter().all(|name| r.matches(action, name)));
+        match rule {
+            Some(rule) => &rule.cmd,
+            None => match action {
+                Action::Open => &self.opener,
+                Action::Page => &self.pager,
+                Action::Edit => &self.editor,
+            },
+        }
+    }
+
+    /// The configured editor opening `target`, whatever file it is.
+    pub fn editor_command(&self, target: &Path) -> anyhow::Result<Vec<String>> {
+        expand(&self.editor, &[target])
+    }
+
+    /// The configured shell split into program and arguments.
+    pub fn shell_command(&self) -> anyhow::Result<Vec<String>> {
+        split(&self.shell)
+    }
+
+    /// The configured shell running `command` with `-c`. `args` become `$1`
+    /// and so on.
+    pub fn shell_run_command(
+        &self,
+        command: &str,
+        args: &[PathBuf],
+    ) -> anyhow::Result<Vec<String>> {
+        let mut shell = split(&self.shell)?;
+        let name = shell.first().cloned().unwrap_or_default();
+        shell.extend([String::from("-c"), command.to_string(), name]);
+        shell.extend(args.iter().map(|a| a.to_string_lossy().to_string()));
+        Ok(shell)
+    }
+}
+
+/// Splits a command template into arguments and substitutes `{}` with the
+/// target paths. A `{}` argument of its own becomes one argument per target.
+/// The paths are appended when the template has no `{}`.
+fn expand(template: &str, targets: &[&Path]) -> anyhow::Result<Vec<String>> {
+    let targets: Vec<String> = targets
+        .iter()
+        .map(|t| t.to_string_lossy().to_string())
+        .collect();
+    let args = split(template)?;
+    if !args.iter().any(|a| a.contains("{}")) {
+        return Ok(args.into_iter().chain(targets).collect());
+    }
+    let mut expanded = Vec::new();
+    for arg in args {
+        if arg == "{}" {
+            expanded.extend(targets.iter().cloned());
+        } else {
+            expanded.push(arg.replace("{}", &targets.join(" ")));
+        }
+    }
+    Ok(expanded)
+}
+
+/// Parses a `[keys]` entry. The action is a command of the `:` command line,
+/// with or without the `:`, and an empty one unbinds the keys.
+fn binding(keys: &str, action: &str) -> anyhow::Result<(Vec<Key>, Option<OpType>)> {
+    let keys = keymap::parse_keys(keys)?;
+    let action = action.trim();
+    let action = action.strip_prefix(':').unwrap_or(action);
+    match action {
+        "" => Ok((keys, None)),
+        action => Ok((keys, Some(command::parse(action)?))),
+    }
+}
+
+/// Splits a command shell-style, so `EDITOR="code -w"` runs `code` with `-w`.
+fn split(command: &str) -> anyhow::Result<Vec<String>> {
+    shell_words::split(command).with_context(|| format!("invalid command: {}", command))
+}
+
+fn env_or(vars: &[&str], default: &str) -> String {
+    vars.iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| String::from(default))
+}
+
+fn config_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("noicer").join("config"))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    editor: Option<String>,
+    pager: Option<String>,
+    shell: Option<String>,
+    opener: Option<String>,
+    scrolloff: Option<usize>,
+    stream_above: Option<u64>,
+    timeout: Option<u64>,
+    #[serde(default)]
+    keys: BTreeMap<String, String>,
+    #[serde(default, rename = "rule")]
+    rules: Vec<RuleFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default = "default_actions", rename = "on")]
+    actions: Vec<Action>,
+    #[serde(default)]
+    ext: Vec<String>,
+    #[serde(default)]
+    glob: Vec<String>,
+    #[serde(default)]
+    mime: Vec<String>,
+    cmd: String,
+}
+
+fn default_actions() -> Vec<Action> {
+    vec![Action::Open]
+}
+
+impl RuleFile {
+    fn compile(self) -> anyhow::Result<Rule> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &self.glob {
+            builder.add(Glob::new(pattern)?);
+        }
+        Ok(Rule {
+            actions: self.actions,
+            ext: self.ext,
+            glob: builder.build()?,
+            mime: self.mime,
+            cmd: self.cmd,
+        })
+    }
+}
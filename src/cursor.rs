@@ -1,4 +1,11 @@
-use std::path::{Path, PathBuf};
+use std::{
+    ops::Range,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::matcher::Matcher;
 
 #[derive(Clone)]
 pub enum Sort {
@@ -8,15 +15,68 @@ pub enum Sort {
     Time,
 }
 
+/// A listing entry together with the metadata used for sorting, read once
+/// instead of on every comparison.
+#[derive(Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub mtime: SystemTime,
+    pub mode: u32,
+    pub is_dir: bool,
+    pub link: Option<Link>,
+}
+
+/// An archive member that is a link rather than a file of its own.
+#[derive(Clone)]
+pub enum Link {
+    /// A symlink to the path as stored in the archive.
+    Symlink(PathBuf),
+    /// A hard link to the member listed under this path.
+    Hard(PathBuf),
+}
+
+impl Entry {
+    pub fn from_path(path: PathBuf) -> Entry {
+        let metadata = std::fs::metadata(&path).or_else(|_| std::fs::symlink_metadata(&path));
+        match metadata {
+            Ok(m) => Entry {
+                size: m.len(),
+                mtime: m.modified().unwrap_or(UNIX_EPOCH),
+                mode: m.permissions().mode(),
+                is_dir: m.is_dir(),
+                link: None,
+                path,
+            },
+            Err(_) => Entry {
+                path,
+                size: 0,
+                mtime: UNIX_EPOCH,
+                mode: 0,
+                is_dir: false,
+                link: None,
+            },
+        }
+    }
+}
+
 pub(crate) trait Cursor {
     fn init(&mut self, cwd: &Path) -> anyhow::Result<()>;
+    fn read_file_content(&mut self, path: &Path) -> anyhow::Result<Vec<u8>>;
     fn move_down(&mut self, n: i32) -> anyhow::Result<()>;
     fn move_up(&mut self, n: i32) -> anyhow::Result<()>;
     fn move_in(&mut self) -> anyhow::Result<()>;
     fn move_out(&mut self) -> anyhow::Result<()>;
     fn move_bottom(&mut self) -> anyhow::Result<()>;
     fn move_top(&mut self) -> anyhow::Result<()>;
+
+    /// Goes to `dir`, selecting the entry selected when it was last left.
+    fn move_to(&mut self, dir: &Path) -> anyhow::Result<()> {
+        anyhow::bail!("can't go to {} from here", dir.display())
+    }
     fn toggle_hidden_files(&mut self) -> anyhow::Result<()>;
+    /// Whether hidden entries are left out of the listing.
+    fn hides_hidden_files(&self) -> bool;
     fn toggle_case_sensitivity(&mut self) -> anyhow::Result<()>;
     fn sort(&self) -> Sort;
     fn sort_dir(&mut self) -> anyhow::Result<()>;
@@ -25,18 +85,133 @@ pub(crate) trait Cursor {
     fn sort_time(&mut self) -> anyhow::Result<()>;
     fn search(&mut self, pattern: &str) -> anyhow::Result<()>;
     fn matching_siblings(&mut self, pattern: &str) -> anyhow::Result<Vec<PathBuf>>;
+
+    /// Moves `n` matches forward, or backward when `n` is negative, wrapping
+    /// around at either end of the listing.
+    fn search_next(&mut self, pattern: &str, n: i32) -> anyhow::Result<()> {
+        let matches = self.matching_siblings(pattern)?;
+        if matches.is_empty() || n == 0 {
+            return Ok(());
+        }
+        // i64, so that counts up to i32::MAX can't overflow
+        let (n, len) = (n as i64, matches.len() as i64);
+        let target = match matches.iter().position(|p| p == &self.selected()) {
+            Some(i) => i as i64 + n,
+            None => {
+                let siblings = self.siblings(self.current_dir())?;
+                let pos = self.pos()? as i64;
+                let positions: Vec<i64> = matches
+                    .iter()
+                    .filter_map(|m| siblings.iter().position(|s| s == m))
+                    .map(|p| p as i64)
+                    .collect();
+                if n > 0 {
+                    let next = positions.iter().position(|&p| p > pos).unwrap_or(0) as i64;
+                    next + n - 1
+                } else {
+                    let prev = positions.iter().rposition(|&p| p < pos);
+                    prev.map(|p| p as i64).unwrap_or(len - 1) + n + 1
+                }
+            }
+        };
+        self.select(&matches[target.rem_euclid(len) as usize])
+    }
+
+    /// The 1-based index of the selection among the matches of `pattern` and
+    /// the total number of matches.
+    fn match_position(&mut self, pattern: &str) -> anyhow::Result<(Option<usize>, usize)> {
+        let matches = self.matching_siblings(pattern)?;
+        let index = matches.iter().position(|p| p == &self.selected());
+        Ok((index.map(|i| i + 1), matches.len()))
+    }
+
+    fn filter(&self) -> Option<String>;
+    fn set_filter(&mut self, pattern: &str) -> anyhow::Result<()>;
+    fn select(&mut self, path: &Path) -> anyhow::Result<()>;
     fn selected(&self) -> PathBuf;
     fn current_dir(&self) -> PathBuf;
     fn start_dir(&self) -> PathBuf;
     fn parent(&self) -> PathBuf;
-    fn current_siblings(&mut self) -> anyhow::Result<Option<Vec<PathBuf>>>;
+    /// The number of entries listed in the current directory.
+    fn current_len(&mut self) -> anyhow::Result<usize>;
+    /// The entries listed in the current directory within `range`, so that
+    /// only the rows on screen are copied out of a large listing.
+    fn current_window(&mut self, range: Range<usize>) -> anyhow::Result<Vec<PathBuf>>;
     fn siblings(&mut self, path: PathBuf) -> anyhow::Result<Vec<PathBuf>>;
-    fn hidden(&self, path: &Path) -> bool;
-    fn sort_by_casing(&self, siblings: &mut [PathBuf]);
-    fn sort_by_dir(&self, siblings: &mut [PathBuf]);
-    fn sort_by_name(&self, siblings: &mut [PathBuf]);
-    fn sort_by_time(&self, siblings: &mut [PathBuf]);
-    fn sort_by_size(&self, siblings: &mut [PathBuf]);
+    /// Everything in `dir` with its metadata, regardless of the hidden and
+    /// filter settings.
+    fn entries(&mut self, dir: &Path) -> anyhow::Result<Vec<Entry>>;
+
+    /// Whether `path` must not be written to disk, see
+    /// `ArchiveTree::is_unsafe`.
+    fn is_unsafe(&self, _path: &Path) -> bool {
+        false
+    }
+
+    /// Extra information shown after the name of `path` in the listing.
+    fn annotation(&self, _path: &Path) -> Option<String> {
+        None
+    }
+    fn refresh(&mut self) -> anyhow::Result<()>;
+    fn invalidate(&mut self, dir: &Path) -> anyhow::Result<()>;
     fn pos(&mut self) -> anyhow::Result<i32>;
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
+}
+
+/// How a cursor lists directories: whether hidden entries are left out and
+/// how the rest is sorted.
+#[derive(Clone)]
+pub struct Settings {
+    pub hide: bool,
+    pub casing: bool,
+    pub sort: Sort,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self {
+            hide: true,
+            casing: false,
+            sort: Sort::Name,
+        }
+    }
+
+    /// The paths of `entries` in listing order, leaving out hidden entries
+    /// and those `filter` doesn't match. An invalid filter matches nothing.
+    pub fn view(&self, entries: &[Entry], filter: Option<&str>) -> Vec<PathBuf> {
+        let filter = filter.map(Matcher::new);
+        let mut siblings: Vec<&Entry> = entries
+            .iter()
+            .filter(|entry| !(self.hide && hidden(&entry.path)))
+            .filter(|entry| match &filter {
+                Some(Ok(matcher)) => matcher.is_match(&entry.path),
+                Some(Err(_)) => false,
+                None => true,
+            })
+            .collect();
+        match self.sort {
+            Sort::Dir => siblings.sort_by_key(|e| std::cmp::Reverse(e.is_dir)),
+            Sort::Name => siblings.sort_by(|a, b| a.path.file_name().cmp(&b.path.file_name())),
+            Sort::Size => siblings.sort_by_key(|e| e.size),
+            Sort::Time => siblings.sort_by_key(|e| e.mtime),
+        }
+        if self.casing {
+            siblings.sort_by_cached_key(|e| e.path.to_str().unwrap_or("").to_lowercase());
+        }
+        siblings.into_iter().map(|e| e.path.clone()).collect()
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `path` is a dotfile.
+fn hidden(path: &Path) -> bool {
+    path.file_name()
+        .unwrap_or_default()
+        .to_str()
+        .unwrap_or("")
+        .starts_with('.')
 }
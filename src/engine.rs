@@ -1,38 +1,73 @@
-use crossterm::event::{KeyCode, KeyEvent};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::keymap::{Key, Keymap, Lookup};
 
 pub struct Engine {
-    buffer: String,
+    keymap: Keymap,
+    // keys typed so far of a sequence that isn't complete yet
+    pending: Vec<Key>,
+    // when the pending sequence was last extended
+    pending_since: Option<Instant>,
+    // how long to wait for the next key of an ambiguous sequence
+    timeout: Duration,
+    // the count typed before a sequence, like the 5 of `5j`
+    count: Option<i32>,
+    // an operation waiting to run after the one just returned
+    queued: Option<OpType>,
     mode: Mode,
     search_term: String,
+    last_search: String,
+    command_line: String,
+    // entered command lines, oldest first
+    history: Vec<String>,
+    // the history entry shown, `history.len()` for the line being typed
+    history_pos: usize,
+    // the line being typed while going through the history
+    draft: String,
 }
 
 #[derive(PartialEq)]
 pub enum Mode {
     Normal,
     Search,
+    Command,
 }
 
 impl Engine {
     pub fn new() -> Self {
         Engine {
-            buffer: String::new(),
+            keymap: Keymap::new(),
+            pending: Vec::new(),
+            pending_since: None,
+            timeout: Duration::from_millis(1000),
+            count: None,
+            queued: None,
             mode: Mode::Normal,
             search_term: String::new(),
+            last_search: String::new(),
+            command_line: String::new(),
+            history: Vec::new(),
+            history_pos: 0,
+            draft: String::new(),
         }
     }
 
     pub fn push(&mut self, ke: KeyEvent) -> anyhow::Result<Option<OpType>> {
+        if self.mode == Mode::Normal {
+            return Ok(self.handle_key(Key::from(ke)));
+        }
         match ke.code {
+            KeyCode::Char(_) if ke.modifiers.contains(KeyModifiers::CONTROL) => Ok(None),
+
             KeyCode::Char(c) => {
-                if self.mode == Mode::Normal {
-                    let op = self.handle_char(c)?;
-                    Ok(op)
-                } else if self.mode == Mode::Search {
+                if self.mode == Mode::Search {
                     self.search_term.push(c);
-                    Ok(None)
-                } else {
-                    Ok(None)
+                } else if self.mode == Mode::Command {
+                    self.command_line.push(c);
                 }
+                Ok(None)
             }
 
             KeyCode::Backspace => {
@@ -43,6 +78,12 @@ impl Engine {
                         self.clear_search_term();
                         self.toggle_search();
                     }
+                } else if self.mode == Mode::Command {
+                    if !self.command_line.is_empty() {
+                        self.command_line.pop();
+                    } else {
+                        self.toggle_command();
+                    }
                 }
                 Ok(None)
             }
@@ -52,6 +93,9 @@ impl Engine {
                     self.clear_search_term();
                     self.toggle_search();
                     Ok(Some(OpType::Opabort))
+                } else if self.mode == Mode::Command {
+                    self.toggle_command();
+                    Ok(Some(OpType::Opabort))
                 } else {
                     Ok(None)
                 }
@@ -59,90 +103,172 @@ impl Engine {
 
             KeyCode::Enter => {
                 if self.mode == Mode::Search {
+                    self.last_search = self.search_term.clone();
                     self.clear_search_term();
                     self.toggle_search();
                     Ok(Some(OpType::Opabort))
+                } else if self.mode == Mode::Command {
+                    let line = std::mem::take(&mut self.command_line);
+                    if !line.trim().is_empty() && self.history.last() != Some(&line) {
+                        self.history.push(line.clone());
+                    }
+                    self.toggle_command();
+                    Ok(Some(OpType::Opcommand(line)))
                 } else {
                     Ok(None)
                 }
             }
 
+            KeyCode::Up if self.mode == Mode::Command => {
+                if self.history_pos == self.history.len() {
+                    self.draft = self.command_line.clone();
+                }
+                self.history_pos = self.history_pos.saturating_sub(1);
+                if let Some(line) = self.history.get(self.history_pos) {
+                    self.command_line = line.clone();
+                }
+                Ok(None)
+            }
+
+            KeyCode::Down if self.mode == Mode::Command => {
+                self.history_pos = (self.history_pos + 1).min(self.history.len());
+                self.command_line = match self.history.get(self.history_pos) {
+                    Some(line) => line.clone(),
+                    None => self.draft.clone(),
+                };
+                Ok(None)
+            }
+
+            KeyCode::Tab if self.mode == Mode::Command => Ok(Some(OpType::Opcomplete)),
+
             _ => Ok(None),
         }
     }
 
-    fn handle_char(&mut self, c: char) -> anyhow::Result<Option<OpType>> {
-        self.buffer.push(c);
-        let op = self.buffer.pop();
-        let mut res = None;
-        if let Some(op) = op {
-            res = self.parse_op(&op.to_string())?;
-        }
-        if res != Some(OpType::None) || self.buffer.len() > 2 {
-            self.buffer = String::new();
+    /// Feeds a key of normal mode to the keymap. Digits before a sequence
+    /// are its count.
+    fn handle_key(&mut self, key: Key) -> Option<OpType> {
+        if key == Key::new(KeyCode::Esc, KeyModifiers::NONE) {
+            self.reset();
+            return None;
         }
-        if res == Some(OpType::None) {
-            self.buffer
-                .push(op.ok_or(anyhow::anyhow!("shouldn't happen"))?);
+        if let Some(digit) = key.digit().filter(|_| self.pending.is_empty()) {
+            if digit != '0' || self.count.is_some() {
+                let digit = digit.to_digit(10).unwrap_or(0) as i32;
+                // absurd counts are clamped rather than dropped
+                self.count = Some(
+                    self.count
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(digit),
+                );
+                return None;
+            }
         }
-        Ok(res)
-    }
-
-    fn parse_op(&mut self, op: &str) -> anyhow::Result<Option<OpType>> {
-        let op = match op {
-            // simple
-            "q" => OpType::Opq,
-            "G" => OpType::OpG,
-            "j" => {
-                if !self.buffer.is_empty() {
-                    let n = self.buffer.parse::<i32>().unwrap_or(1);
-                    OpType::Opj(n)
-                } else {
-                    OpType::Opj(1)
-                }
+
+        self.pending.push(key);
+        match self.keymap.lookup(&self.pending) {
+            Lookup::Bound(op) => {
+                let op = op.clone();
+                Some(self.finish(op))
             }
-            "k" => {
-                if !self.buffer.is_empty() {
-                    let n = self.buffer.parse::<i32>().unwrap_or(1);
-                    OpType::Opk(n)
-                } else {
-                    OpType::Opk(1)
-                }
+            Lookup::Prefix(_) => {
+                self.pending_since = Some(Instant::now());
+                None
             }
-
-            "h" => OpType::Oph,
-            "l" => OpType::Opl,
-
-            "." => OpType::Opdot,
-            "~" => OpType::Opcasing,
-            "d" => OpType::Opsortdir,
-            "n" => OpType::Opsortname,
-            "s" => OpType::Opsortsize,
-            "t" => OpType::Opsorttime,
-            "/" => OpType::Opslash,
-            "p" => OpType::Oppage,
-            "e" => OpType::Opedit,
-            "!" => OpType::Opbang,
-            "?" => OpType::Opquestion,
-
-            // complex
-            "g" => {
-                if !self.buffer.is_empty() {
-                    let o = self.buffer.pop().unwrap_or_default().to_string();
-
-                    if o == "g" {
-                        OpType::Opgg
-                    } else {
-                        OpType::None
+            Lookup::Unbound => {
+                self.pending.pop();
+                // a bound prefix runs as if it had timed out, and the key
+                // that didn't continue it starts a sequence of its own
+                let prefix = match self.keymap.lookup(&self.pending) {
+                    Lookup::Prefix(Some(op)) => Some(op.clone()),
+                    _ => None,
+                };
+                let had_pending = !self.pending.is_empty();
+                match prefix {
+                    Some(op) => {
+                        let op = self.finish(op);
+                        self.queued = self.handle_key(key);
+                        Some(op)
+                    }
+                    None if had_pending => {
+                        self.reset();
+                        self.handle_key(key)
+                    }
+                    None => {
+                        self.reset();
+                        None
                     }
-                } else {
-                    OpType::None
                 }
             }
+        }
+    }
+
+    /// An operation due without a key press: one queued behind the last,
+    /// or the binding of an ambiguous sequence once no key has followed it
+    /// for the timeout.
+    pub fn expire(&mut self) -> Option<OpType> {
+        if let Some(op) = self.queued.take() {
+            return Some(op);
+        }
+        let since = self.pending_since?;
+        if since.elapsed() < self.timeout {
+            return None;
+        }
+        match self.keymap.lookup(&self.pending) {
+            Lookup::Prefix(Some(op)) | Lookup::Bound(op) => {
+                let op = op.clone();
+                Some(self.finish(op))
+            }
+            _ => {
+                self.reset();
+                None
+            }
+        }
+    }
+
+    /// How long to wait for a key before `expire` has to be called, at
+    /// most `max`.
+    pub fn wait(&self, max: Duration) -> Duration {
+        match (self.queued.as_ref(), self.pending_since) {
+            (Some(_), _) => Duration::ZERO,
+            (None, Some(since)) => self.timeout.saturating_sub(since.elapsed()).min(max),
+            (None, None) => max,
+        }
+    }
+
+    /// Applies the count to `op` and starts over with the next sequence.
+    /// Operations without a count of their own get it wrapped around them.
+    fn finish(&mut self, op: OpType) -> OpType {
+        let count = self.count.take();
+        self.reset();
+        match (op, count) {
+            (op, None) => op,
+            (OpType::Opj(_), Some(n)) => OpType::Opj(n),
+            (OpType::Opk(_), Some(n)) => OpType::Opk(n),
+            (op, Some(n)) => OpType::Opcount(n, Box::new(op)),
+        }
+    }
+
+    /// The count typed so far, while the sequence it is for isn't complete.
+    pub fn count(&self) -> Option<i32> {
+        self.count
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.pending_since = None;
+        self.count = None;
+    }
 
-            _ => OpType::None,
-        };
-        Ok(Some(op))
+    /// Binds `keys` to `op` on top of the defaults, or unbinds them when
+    /// `op` is `None`.
+    pub fn bind(&mut self, keys: &[Key], op: Option<OpType>) {
+        self.keymap.bind(keys, op);
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
     }
 
     pub fn toggle_search(&mut self) {
@@ -150,7 +276,20 @@ impl Engine {
             Mode::Normal => {
                 self.mode = Mode::Search;
             }
-            Mode::Search => {
+            Mode::Search | Mode::Command => {
+                self.mode = Mode::Normal;
+            }
+        }
+    }
+
+    pub fn toggle_command(&mut self) {
+        match self.mode {
+            Mode::Normal => {
+                self.history_pos = self.history.len();
+                self.mode = Mode::Command;
+            }
+            Mode::Search | Mode::Command => {
+                self.command_line = String::new();
                 self.mode = Mode::Normal;
             }
         }
@@ -162,15 +301,27 @@ impl Engine {
 
     pub fn is_search(&self) -> bool {
         match self.mode {
-            Mode::Normal => false,
+            Mode::Normal | Mode::Command => false,
             Mode::Search => true,
         }
     }
 
+    pub fn command_line(&self) -> &str {
+        &self.command_line
+    }
+
+    pub fn set_command_line(&mut self, line: String) {
+        self.command_line = line;
+    }
+
     pub fn search_term(&self) -> &str {
         &self.search_term
     }
 
+    pub fn last_search(&self) -> &str {
+        &self.last_search
+    }
+
     pub fn clear_search_term(&mut self) {
         self.search_term = String::new();
     }
@@ -182,13 +333,23 @@ impl Default for Engine {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum OpType {
     Opq,
     OpG,
     Opgg,
+    OpH,
+    OpM,
+    OpL,
+    Opctrld,
+    Opctrlu,
+    Opctrlf,
+    Opctrlb,
+    Opctrll,
     Opj(i32),
     Opk(i32),
+    // an operation typed with a count, like `10G` or `3l`
+    Opcount(i32, Box<OpType>),
     Oph,
     Opl,
     Opdot,
@@ -198,8 +359,38 @@ pub enum OpType {
     Opsortsize,
     Opsorttime,
     Opslash,
+    Opn,
+    OpN,
+    Opcolon,
+    Opcommand(String),
+    Opcomplete,
+    Opfilter(String),
+    Opcd(String),
+    Opsethidden(bool),
+    Opshell(String),
     Oppage,
     Opedit,
+    Opmark,
+    Opmarkall,
+    Opinvertmarks,
+    Opclearmarks,
+    Opyank,
+    Opcut,
+    Oppaste,
+    Optrash,
+    Opdelete,
+    Optrashview,
+    // the new name, asked for when not given
+    Oprename(Option<String>),
+    OpR,
+    Oprestore,
+    Opbulkrename,
+    Opnewfile(Option<String>),
+    Opnewdir(Option<String>),
+    Opsymlink(Option<String>),
+    Ophardlink(Option<String>),
+    Opextract,
+    Opcompress,
     Opbang,
     Opabort,
     Opquestion,
@@ -1,50 +1,83 @@
-use std::{io::Write, path::Path};
+use std::{
+    collections::BTreeSet,
+    ffi::OsString,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use std::process::Command;
+use std::process::{Command, Stdio};
 
+use anyhow::Context;
 use crossterm::{
     event::{self, Event},
     execute, queue, style,
     terminal::{self, ClearType},
 };
-use tempfile::NamedTempFile;
+use tempfile::{NamedTempFile, TempPath};
 
+use crate::archive_cursor::{ArchiveCursor, Members};
+use crate::archive_tree::{self, ArchiveTree};
+use crate::command;
+use crate::compress::{self, Format};
+use crate::compression::{self, Compression};
+use crate::config::{Action, Config};
 use crate::cursor::{Cursor, Sort};
 use crate::engine::{Engine, Mode, OpType};
+use crate::extract::{Extractor, Overwrite};
 use crate::file_cursor::FileCursor;
-use crate::lines::Lines;
-use crate::tar_cursor::TarCursor;
+use crate::fileops::{self, Conflict, Transfer};
+use crate::lines::{Lines, Viewport};
+use crate::prompt;
+use crate::rename;
+use crate::tar_members::TarMembers;
+use crate::trash;
+use crate::trash_cursor::TrashCursor;
+use crate::watcher::Watcher;
+use crate::zip_members::ZipMembers;
 
-pub struct Config {
-    pub editor: String,
-    pub pager: String,
-    pub shell: String,
+/// An archive being browsed. Archives nested in another archive are read
+/// from a copy in a temp file, which lives as long as the entry.
+pub struct Archive {
+    pub path: PathBuf,
+    pub temp: Option<TempPath>,
+    // opened by `l`, handed over to the cursors of the main loop
+    pub(crate) cursor: Option<Box<dyn Cursor>>,
 }
 
-impl Config {
-    fn new() -> Config {
-        Config {
-            editor: String::from("vim"),
-            pager: String::from("less"),
-            shell: String::from("bash"),
-        }
-    }
+/// Paths yanked with `yy` or cut with `dd`, waiting to be pasted.
+pub struct Clipboard {
+    pub paths: Vec<PathBuf>,
+    pub cut: bool,
 }
 
 pub struct State {
     pub config: Config,
     pub running: bool,
     pub status_bar: bool,
-    pub tar: bool,
+    // shown on the bottom row until the next key press
+    pub message: Option<String>,
+    // absolute paths, virtual ones inside archives
+    pub marks: BTreeSet<PathBuf>,
+    pub clipboard: Option<Clipboard>,
+    pub trash: bool,
+    pub archives: Vec<Archive>,
+    pub viewport: Viewport,
 }
 
 impl State {
-    fn new() -> State {
+    fn new(config: Config) -> State {
         State {
-            config: Config::new(),
+            viewport: Viewport::new(config.scrolloff),
+            config,
             running: true,
             status_bar: false,
-            tar: false,
+            message: None,
+            marks: BTreeSet::new(),
+            clipboard: None,
+            trash: false,
+            archives: Vec::new(),
         }
     }
 }
@@ -53,24 +86,46 @@ pub fn run<W>(w: &mut W) -> anyhow::Result<()>
 where
     W: Write,
 {
-    execute!(w, terminal::EnterAlternateScreen)?;
+    let config = Config::load()?;
 
-    terminal::enable_raw_mode()?;
+    let _terminal = Terminal::enter(w)?;
 
-    let mut state = State::new();
+    let mut state = State::new(config);
     let mut file_cursor = FileCursor::new();
-    let mut tar_cursor = TarCursor::new();
+    // one cursor per entry in `state.archives`, innermost last
+    let mut archive_cursors: Vec<Box<dyn Cursor>> = Vec::new();
+    let mut trash_cursor: Option<TrashCursor> = None;
     let mut engine = Engine::new();
+    for (keys, op) in &state.config.keys {
+        engine.bind(keys, op.clone());
+    }
+    engine.set_timeout(Duration::from_millis(state.config.timeout));
 
     let cwd = std::env::current_dir()?;
 
     file_cursor.init(&cwd)?;
 
+    // live refresh is best effort, browsing works without it
+    let mut watcher = Watcher::new().ok();
+    // a change seen while browsing the trash or an archive
+    let mut changed: Option<PathBuf> = None;
+
     loop {
         if !state.running {
             break;
         }
 
+        if let Some(watcher) = watcher.as_mut() {
+            let dir = file_cursor.current_dir();
+            if watcher.watch(&dir)? {
+                file_cursor.set_watched(watcher.watched());
+                file_cursor.invalidate(&dir)?;
+            }
+        }
+        if let Some(dir) = changed.take() {
+            file_cursor.invalidate(&dir)?;
+        }
+
         queue!(
             w,
             style::ResetColor,
@@ -79,33 +134,69 @@ where
             crossterm::cursor::MoveTo(1, 1)
         )?;
 
-        let lines = match state.tar {
-            true => {
-                tar_cursor.init(&file_cursor.selected())?;
-                Lines::new().format(&mut tar_cursor)?
+        let (term_width, term_height) = terminal::size()?;
+        // header, blank line and status bar
+        state.viewport.height = (term_height as usize).saturating_sub(4).max(1);
+
+        archive_cursors.truncate(state.archives.len());
+        for archive in &mut state.archives[archive_cursors.len()..] {
+            archive_cursors.extend(archive.cursor.take());
+        }
+
+        if !state.trash {
+            // restoring changes directories that weren't watched meanwhile
+            if trash_cursor.take().is_some() {
+                file_cursor.invalidate(&file_cursor.current_dir())?;
             }
-            false => Lines::new().format(&mut file_cursor)?,
-        };
+        } else if trash_cursor.is_none() {
+            let mut cursor = TrashCursor::new();
+            cursor.init(&trash::files_dir()?)?;
+            trash_cursor = Some(cursor);
+        }
 
-        let cursor: &mut dyn Cursor = if state.tar {
-            &mut tar_cursor
-        } else {
-            &mut file_cursor
+        // archives can be entered from the trash, so they come first
+        let cursor: &mut dyn Cursor = match (archive_cursors.last_mut(), trash_cursor.as_mut()) {
+            (Some(archive_cursor), _) => archive_cursor.as_mut(),
+            (None, Some(trash_cursor)) => trash_cursor,
+            (None, None) => &mut file_cursor,
         };
 
+        let lines = Lines::new().format(cursor, &mut state.viewport, &state.marks)?;
+
         for line in lines {
             queue!(w, style::Print(&line), crossterm::cursor::MoveToNextLine(1))?;
         }
 
-        let (term_width, term_height) = terminal::size()?;
         if engine.mode() == &Mode::Search {
             queue!(
                 w,
                 crossterm::cursor::MoveTo(0, term_height - 1),
                 style::Print(format!("/{}", &engine.search_term()))
             )?;
-        } else if state.status_bar {
-            let status_bar = create_status_bar(cursor, &engine);
+        } else if engine.mode() == &Mode::Command {
+            // completion candidates go on the row above
+            if let Some(message) = state.message.as_ref() {
+                queue!(
+                    w,
+                    crossterm::cursor::MoveTo(0, term_height.saturating_sub(2)),
+                    terminal::Clear(ClearType::CurrentLine),
+                    style::Print(message)
+                )?;
+            }
+            queue!(
+                w,
+                crossterm::cursor::MoveTo(0, term_height - 1),
+                style::Print(format!(":{}", &engine.command_line()))
+            )?;
+        } else if let Some(message) = state.message.as_ref() {
+            queue!(
+                w,
+                crossterm::cursor::MoveTo(0, term_height - 1),
+                style::Print(message)
+            )?;
+        } else if state.status_bar || engine.count().is_some() {
+            let marked = marked(&state, cursor).len();
+            let status_bar = create_status_bar(cursor, &engine, marked)?;
             queue!(
                 w,
                 crossterm::cursor::MoveTo(0, term_height - 1),
@@ -118,7 +209,25 @@ where
 
         w.flush()?;
 
-        match handle_keypress(cursor, &mut engine) {
+        // wait for a key press or a change in the watched directory
+        let op = loop {
+            if let Some(op) = engine.expire() {
+                break Ok(Some(op));
+            }
+            if event::poll(engine.wait(Duration::from_millis(250)))? {
+                state.message = None;
+                break handle_keypress(cursor, &mut engine);
+            }
+            if let Some(dir) = watcher.as_mut().map(|w| w.changed()).transpose()?.flatten() {
+                if state.archives.is_empty() && !state.trash {
+                    cursor.invalidate(&dir)?;
+                    break Ok(None);
+                }
+                changed = Some(dir);
+            }
+        };
+
+        match op {
             Ok(Some(op)) => {
                 let _res = run_op(&mut state, op, cursor, &mut engine)?;
             }
@@ -127,14 +236,33 @@ where
         }
     }
 
-    execute!(
-        w,
-        style::ResetColor,
-        crossterm::cursor::Show,
-        terminal::LeaveAlternateScreen
-    )?;
+    Ok(())
+}
+
+/// Raw mode on the alternate screen for as long as it lives. The terminal
+/// is restored on drop, so an error or panic in the loop doesn't leave it
+/// unusable.
+struct Terminal;
+
+impl Terminal {
+    fn enter<W: Write>(w: &mut W) -> anyhow::Result<Terminal> {
+        let guard = Terminal;
+        execute!(w, terminal::EnterAlternateScreen)?;
+        terminal::enable_raw_mode()?;
+        Ok(guard)
+    }
+}
 
-    Ok(terminal::disable_raw_mode()?)
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(
+            io::stdout(),
+            style::ResetColor,
+            crossterm::cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
 }
 
 fn handle_keypress(cursor: &mut dyn Cursor, engine: &mut Engine) -> anyhow::Result<Option<OpType>> {
@@ -166,29 +294,41 @@ fn run_op(
         OpType::Opj(n) => cursor.move_down(n)?,
         OpType::Opk(n) => cursor.move_up(n)?,
         OpType::Oph => {
-            if state.tar
+            if !state.archives.is_empty()
                 && cursor.selected().parent().unwrap_or(Path::new("")) == cursor.start_dir()
             {
-                state.tar = false
+                // the containing cursor still has the archive selected
+                if let Some(archive) = state.archives.pop() {
+                    state.marks.retain(|m| !m.starts_with(&archive.path));
+                }
+            } else if state.trash
+                && state.archives.is_empty()
+                && cursor.current_dir() == cursor.start_dir()
+            {
+                state.trash = false
             } else {
                 cursor.move_out()?
             }
         }
         OpType::Opl => {
             let selected = cursor.selected();
-            if selected.is_dir() || selected.to_str().unwrap_or("").ends_with('/') {
+            // `..` stands in for the entries of an empty directory
+            if selected.is_dir()
+                || selected.to_str().unwrap_or("").ends_with('/')
+                || selected.ends_with("..")
+            {
                 cursor.move_in()?
-            } else if selected.extension().unwrap_or_default() == "tar" {
-                state.tar = true;
-            } else if state.tar && !selected.ends_with("..") {
-                if let Some(tar_cursor) = cursor.as_any_mut().downcast_mut::<TarCursor>() {
-                    let content = tar_cursor.read_file_content(&selected)?;
-                    let mut temp_file = NamedTempFile::new()?;
-                    temp_file.write_all(&content)?;
-                    run_prog("bat", temp_file.path())?;
+            } else if state.archives.is_empty() && is_archive(&state.config, &selected, &selected) {
+                enter_archive(state, selected, None);
+            } else if !state.archives.is_empty() {
+                let temp_file = temp_copy(cursor, &selected)?;
+                if is_archive(&state.config, &selected, temp_file.path()) {
+                    enter_archive(state, selected, Some(temp_file.into_temp_path()));
+                } else {
+                    run_action(&state.config, Action::Open, &selected, temp_file.path())?;
                 }
             } else {
-                run_prog("bat", &selected)?
+                run_action(&state.config, Action::Open, &selected, &selected)?
             }
         }
         OpType::Opdot => cursor.toggle_hidden_files()?,
@@ -198,35 +338,864 @@ fn run_op(
         OpType::Opsortsize => cursor.sort_size()?,
         OpType::Opsorttime => cursor.sort_time()?,
         OpType::Opslash => engine.toggle_search(),
-        OpType::Oppage => run_prog(&state.config.pager, &cursor.selected())?,
-        OpType::Opedit => run_prog(&state.config.editor, &cursor.selected())?,
-        OpType::Opbang => run_prog(&state.config.shell, &cursor.current_dir())?,
+        OpType::Opn => cursor.search_next(engine.last_search(), 1)?,
+        OpType::OpN => cursor.search_next(engine.last_search(), -1)?,
+        OpType::Opcolon => engine.toggle_command(),
+        OpType::Opcommand(line) => match command::parse(&line) {
+            Ok(op) => return run_op(state, op, cursor, engine),
+            Err(e) => state.message = Some(format!("{:#}", e)),
+        },
+        OpType::Opcomplete => {
+            let (line, candidates) =
+                command::complete(engine.command_line(), &cursor.current_dir());
+            engine.set_command_line(line);
+            if !candidates.is_empty() {
+                state.message = Some(candidates.join("  "));
+            }
+        }
+        OpType::Opfilter(term) => cursor.set_filter(&term)?,
+        OpType::Opcd(arg) => {
+            let path = command::resolve(&arg, &cursor.current_dir());
+            let dir: PathBuf = std::iter::once(PathBuf::from("/"))
+                .chain(archive_tree::normalise(&path))
+                .collect();
+            if !state.archives.is_empty() {
+                state.message = Some(String::from("can't cd inside an archive"));
+            } else if let Err(e) = cursor.move_to(&dir) {
+                state.message = Some(format!("{:#}", e));
+            }
+        }
+        OpType::Opsethidden(show) => {
+            if cursor.hides_hidden_files() == show {
+                cursor.toggle_hidden_files()?
+            }
+        }
+        OpType::Oppage | OpType::Opedit => {
+            let action = match op {
+                OpType::Oppage => Action::Page,
+                _ => Action::Edit,
+            };
+            let targets = targets(state, cursor);
+            if state.archives.is_empty() {
+                match targets.as_slice() {
+                    [target] => run_action(&state.config, action, target, target)?,
+                    targets => run_prog(&state.config.command_many(action, targets)?)?,
+                }
+            } else if action == Action::Edit {
+                // changes to a copy would be lost
+                state.message = Some(String::from("can't edit inside an archive"));
+            } else {
+                // members only exist in the archive, they are paged from copies
+                let mut copies = Vec::new();
+                for target in targets.iter().filter(|t| !is_archive_dir(t)) {
+                    copies.push(temp_copy(cursor, target)?);
+                }
+                let paths: Vec<PathBuf> = copies.iter().map(|c| c.path().to_path_buf()).collect();
+                match (targets.as_slice(), paths.as_slice()) {
+                    ([target], [path]) => run_action(&state.config, action, target, path)?,
+                    (_, []) => (),
+                    (_, paths) => run_prog(&state.config.command_many(action, paths)?)?,
+                }
+            }
+        }
+        OpType::Opmark => {
+            let selected = cursor.selected();
+            if !selected.ends_with("..") && !state.marks.remove(&selected) {
+                state.marks.insert(selected);
+            }
+            cursor.move_down(1)?
+        }
+        OpType::Opmarkall => state.marks.extend(cursor.siblings(cursor.current_dir())?),
+        OpType::Opinvertmarks => {
+            for path in cursor.siblings(cursor.current_dir())? {
+                if !state.marks.remove(&path) {
+                    state.marks.insert(path);
+                }
+            }
+        }
+        OpType::Opclearmarks => state.marks.clear(),
+        OpType::Opyank | OpType::Opcut => {
+            let paths = targets(state, cursor);
+            // trashed entries are restored with `R`, which keeps the trash
+            // info in step
+            if state.archives.is_empty() && !state.trash && !paths.is_empty() {
+                let cut = op == OpType::Opcut;
+                state.message = Some(format!(
+                    "{} {} {}",
+                    if cut { "cut" } else { "yanked" },
+                    paths.len(),
+                    if paths.len() == 1 { "entry" } else { "entries" }
+                ));
+                // the marks are in the clipboard now
+                state.marks.clear();
+                state.clipboard = Some(Clipboard { paths, cut });
+            }
+        }
+        OpType::Oppaste => {
+            if let Some(mut clipboard) = state.clipboard.take() {
+                if !state.archives.is_empty() {
+                    state.clipboard = Some(clipboard);
+                    state.message = Some(String::from("can't paste into an archive"));
+                } else if state.trash {
+                    state.clipboard = Some(clipboard);
+                    state.message = Some(String::from("can't paste into the trash"));
+                } else {
+                    state.message = Some(paste(cursor, &clipboard)?);
+                    // moved paths are gone, what is left can still be pasted
+                    clipboard.paths.retain(|p| p.symlink_metadata().is_ok());
+                    if !clipboard.paths.is_empty() {
+                        state.clipboard = Some(clipboard);
+                    }
+                }
+            }
+        }
+        OpType::Optrash | OpType::Opdelete => {
+            let paths = targets(state, cursor);
+            if state.archives.is_empty() && !paths.is_empty() {
+                // trashing from the trash deletes for good
+                let permanent = state.trash || op == OpType::Opdelete;
+                state.message = Some(delete(cursor, &paths, permanent, state.trash)?);
+                state.marks.retain(|m| m.symlink_metadata().is_ok());
+            }
+        }
+        OpType::Opnewfile(_)
+        | OpType::Opnewdir(_)
+        | OpType::Opsymlink(_)
+        | OpType::Ophardlink(_) => {
+            if state.archives.is_empty() && !state.trash {
+                state.message = create(cursor, &op)?;
+            }
+        }
+        OpType::Optrashview => {
+            if state.archives.is_empty() {
+                state.trash = !state.trash
+            }
+        }
+        OpType::Oprename(ref name) => {
+            let selected = cursor.selected();
+            if state.archives.is_empty() && !state.trash && !selected.ends_with("..") {
+                state.message = rename_selected(state, cursor, &selected, name.as_deref())?;
+            }
+        }
+        // restores in the trash view, bulk renames everywhere else
+        OpType::OpR => {
+            let op = match state.trash {
+                true => OpType::Oprestore,
+                false => OpType::Opbulkrename,
+            };
+            return run_op(state, op, cursor, engine);
+        }
+        OpType::Oprestore => {
+            let paths = targets(state, cursor);
+            if state.trash && state.archives.is_empty() && !paths.is_empty() {
+                state.message = Some(restore(cursor, &paths)?);
+                state.marks.retain(|m| m.symlink_metadata().is_ok());
+            }
+        }
+        OpType::Opbulkrename => {
+            if state.archives.is_empty() && !state.trash {
+                state.message = Some(bulk_rename(state, cursor)?);
+            }
+        }
+        OpType::Opextract => {
+            if let Some(archive) = state.archives.first() {
+                let dir = archive
+                    .path
+                    .parent()
+                    .unwrap_or(Path::new("/"))
+                    .to_path_buf();
+                let targets = targets(state, cursor);
+                if !targets.is_empty() {
+                    state.message = Some(extract(cursor, &targets, &dir)?);
+                }
+            }
+        }
+        OpType::Opcompress => {
+            let targets = targets(state, cursor);
+            if state.archives.is_empty() && !state.trash && !targets.is_empty() {
+                state.message = Some(compress(cursor, &targets)?);
+            }
+        }
+        OpType::Opbang | OpType::Opshell(_) if !state.archives.is_empty() => {
+            // the current directory only exists in the archive
+            state.message = Some(String::from("can't run a shell inside an archive"));
+        }
+        OpType::Opbang => {
+            std::env::set_current_dir(cursor.current_dir())?;
+            let marked = marked(state, cursor);
+            with_marked(&marked, || run_prog(&state.config.shell_command()?))?
+        }
+        OpType::Opshell(command) => {
+            std::env::set_current_dir(cursor.current_dir())?;
+            let marked = marked(state, cursor);
+            let args = state.config.shell_run_command(&command, &marked)?;
+            with_marked(&marked, || run_shell(&args))?;
+            cursor.invalidate(&cursor.current_dir())?
+        }
         OpType::Opquestion => state.status_bar = !state.status_bar,
         // complex
         OpType::Opgg => cursor.move_top()?,
+        OpType::OpH | OpType::OpM | OpType::OpL => {
+            let len = cursor.current_len()?;
+            let row = match op {
+                OpType::OpH => state.viewport.screen_top(),
+                OpType::OpM => state.viewport.screen_middle(len),
+                _ => state.viewport.screen_bottom(len),
+            };
+            cursor.move_top()?;
+            cursor.move_down(row as i32)?
+        }
+        OpType::Opcount(n, op) => match *op {
+            // `10G` and `10gg` go to the 10th entry
+            OpType::OpG | OpType::Opgg => {
+                cursor.move_top()?;
+                cursor.move_down(n - 1)?
+            }
+            // counted from the top or the bottom of the screen
+            OpType::OpH | OpType::OpL => {
+                let len = cursor.current_len()?;
+                let (top, bottom) = (
+                    state.viewport.screen_top(),
+                    state.viewport.screen_bottom(len),
+                );
+                let offset = n as usize - 1;
+                let row = match *op {
+                    OpType::OpH => (top + offset).min(bottom),
+                    _ => bottom.saturating_sub(offset).max(top),
+                };
+                cursor.move_top()?;
+                cursor.move_down(row as i32)?
+            }
+            // descends along the remembered selections, without opening the
+            // file that may be at the end
+            OpType::Opl => {
+                for _ in 0..n {
+                    let selected = cursor.selected();
+                    if !selected.is_dir() && !selected.to_str().unwrap_or("").ends_with('/') {
+                        break;
+                    }
+                    cursor.move_in()?
+                }
+            }
+            OpType::Oph => {
+                for _ in 0..n {
+                    let dir = cursor.current_dir();
+                    run_op(state, OpType::Oph, cursor, engine)?;
+                    if cursor.current_dir() == dir {
+                        break;
+                    }
+                }
+            }
+            OpType::Opn => cursor.search_next(engine.last_search(), n)?,
+            OpType::OpN => cursor.search_next(engine.last_search(), -n)?,
+            OpType::Opctrld | OpType::Opctrlu | OpType::Opctrlf | OpType::Opctrlb => {
+                let rows = match *op {
+                    OpType::Opctrld | OpType::Opctrlu => (state.viewport.height / 2).max(1),
+                    _ => state.viewport.height,
+                };
+                let rows = (rows as i32).saturating_mul(n);
+                match *op {
+                    OpType::Opctrld | OpType::Opctrlf => cursor.move_down(rows)?,
+                    _ => cursor.move_up(rows)?,
+                }
+            }
+            // marks the next `n` entries
+            OpType::Opmark => {
+                for _ in 0..n {
+                    let selected = cursor.selected();
+                    run_op(state, OpType::Opmark, cursor, engine)?;
+                    if cursor.selected() == selected {
+                        break;
+                    }
+                }
+            }
+            op => return run_op(state, op, cursor, engine),
+        },
+        OpType::Opctrld => cursor.move_down((state.viewport.height / 2).max(1) as i32)?,
+        OpType::Opctrlu => cursor.move_up((state.viewport.height / 2).max(1) as i32)?,
+        OpType::Opctrlf => cursor.move_down(state.viewport.height as i32)?,
+        OpType::Opctrlb => cursor.move_up(state.viewport.height as i32)?,
+        OpType::Opctrll => cursor.refresh()?,
         _ => return Ok(false),
     };
     Ok(true)
 }
 
-pub fn run_prog(prog: &str, path: &Path) -> anyhow::Result<()> {
-    let mut out = match path.is_dir() {
-        true => {
-            std::env::set_current_dir(path)?;
-            Command::new(prog)
-                .spawn()
-                .expect("pager command failed to start")
+/// The marked paths the current cursor can act on. Inside an archive these
+/// are the marks below its root, outside the ones that exist on disk.
+fn marked(state: &State, cursor: &dyn Cursor) -> Vec<PathBuf> {
+    match state.archives.is_empty() {
+        true => state
+            .marks
+            .iter()
+            .filter(|m| m.symlink_metadata().is_ok())
+            .cloned()
+            .collect(),
+        false => state
+            .marks
+            .iter()
+            // the archive itself may be marked outside of it
+            .filter(|m| m.starts_with(cursor.start_dir()) && **m != cursor.start_dir())
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Runs `f` with the marked paths in `$NOICER_MARKED`, one per line. Unlike
+/// positional parameters this works for an interactive shell of any kind,
+/// fish included.
+fn with_marked<T>(marked: &[PathBuf], f: impl FnOnce() -> T) -> T {
+    let mut value = OsString::new();
+    for (i, path) in marked.iter().enumerate() {
+        if i > 0 {
+            value.push("\n");
+        }
+        value.push(path);
+    }
+    std::env::set_var("NOICER_MARKED", value);
+    let result = f();
+    std::env::remove_var("NOICER_MARKED");
+    result
+}
+
+/// The marked paths, or the selection when nothing is marked.
+fn targets(state: &State, cursor: &dyn Cursor) -> Vec<PathBuf> {
+    let marks = marked(state, cursor);
+    if !marks.is_empty() {
+        return marks;
+    }
+    let selected = cursor.selected();
+    match selected.ends_with("..") {
+        true => Vec::new(),
+        false => vec![selected],
+    }
+}
+
+/// Copies or moves the clipboard into the current directory and selects the
+/// first pasted entry. Returns the message summing it up.
+fn paste(cursor: &mut dyn Cursor, clipboard: &Clipboard) -> anyhow::Result<String> {
+    let dir = cursor.current_dir();
+    let verb = if clipboard.cut { "moving" } else { "copying" };
+    let total = clipboard.paths.len();
+    let mut transfer = Transfer::new(|target: &Path| {
+        let question = format!(
+            "{} exists: [o]verwrite [s]kip [r]ename, uppercase for all",
+            target.display()
+        );
+        Ok(match prompt::choose(&question, "osrOSR")? {
+            Some(c) => {
+                let conflict = match c.to_ascii_lowercase() {
+                    'o' => Conflict::Overwrite,
+                    'r' => Conflict::Rename,
+                    _ => Conflict::Skip,
+                };
+                (conflict, c.is_ascii_uppercase())
+            }
+            None => (Conflict::Quit, false),
+        })
+    });
+    let result = transfer.run(&clipboard.paths, &dir, clipboard.cut, |i, path| {
+        prompt::show(&format!("{} {}/{}: {}", verb, i + 1, total, path.display()))
+    });
+
+    cursor.invalidate(&dir)?;
+    if clipboard.cut {
+        for parent in clipboard.paths.iter().filter_map(|p| p.parent()) {
+            cursor.invalidate(parent)?;
+        }
+    }
+    if let Some(first) = transfer.done.first() {
+        cursor.select(first)?;
+    }
+
+    let mut message = format!(
+        "{} {} to {}",
+        if clipboard.cut { "moved" } else { "copied" },
+        transfer.done.len(),
+        dir.display()
+    );
+    if transfer.skipped > 0 {
+        message.push_str(&format!(", {} skipped", transfer.skipped));
+    }
+    if let Err(e) = result {
+        message.push_str(&format!(", stopped: {:#}", e));
+    }
+    Ok(message)
+}
+
+/// Moves `paths` to the trash, or deletes them for good after asking when
+/// `permanent` is set. Returns the message summing it up.
+fn delete(
+    cursor: &mut dyn Cursor,
+    paths: &[PathBuf],
+    permanent: bool,
+    in_trash: bool,
+) -> anyhow::Result<String> {
+    if permanent {
+        let what = match paths {
+            [path] => path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            paths => format!("{} entries", paths.len()),
+        };
+        let question = format!("delete {} permanently? [y/N]", what);
+        if prompt::choose(&question, "y")?.is_none() {
+            return Ok(String::from("delete cancelled"));
+        }
+    }
+
+    let mut done = 0;
+    let mut result = Ok(());
+    for path in paths {
+        let deleted = match (permanent, in_trash) {
+            (true, true) => trash::purge(path),
+            (true, false) => fileops::remove(path),
+            (false, _) => trash::trash(path).map(|_| ()),
+        };
+        if let Err(e) = deleted {
+            result = Err(e);
+            break;
+        }
+        done += 1;
+    }
+    for parent in paths.iter().filter_map(|p| p.parent()) {
+        cursor.invalidate(parent)?;
+    }
+
+    let mut message = format!("{} {}", if permanent { "deleted" } else { "trashed" }, done);
+    if let Err(e) = result {
+        message.push_str(&format!(", stopped: {:#}", e));
+    }
+    Ok(message)
+}
+
+/// Renames `path` to `new`, or to a name edited on the bottom row, which
+/// starts out as the current name with the cursor before the extension.
+/// Returns the message, if any.
+fn rename_selected(
+    state: &mut State,
+    cursor: &mut dyn Cursor,
+    path: &Path,
+    new: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let pos = match path.is_dir() {
+        true => name.chars().count(),
+        false => name[..fileops::stem_len(&name)].chars().count(),
+    };
+    let new = match new {
+        Some(new) => Some(new.to_string()),
+        None => prompt::input_at("rename: ", &name, pos)?,
+    };
+    let target = match new {
+        Some(new) if !new.is_empty() && new != name => path.with_file_name(new),
+        _ => return Ok(None),
+    };
+    if target.symlink_metadata().is_ok() {
+        return Ok(Some(format!("{} exists", target.display())));
+    }
+
+    let result = fileops::move_path(path, &target);
+    for dir in [path.parent(), target.parent()].into_iter().flatten() {
+        cursor.invalidate(dir)?;
+    }
+    if let Err(e) = result {
+        return Ok(Some(format!("rename failed: {:#}", e)));
+    }
+    if state.marks.remove(path) {
+        state.marks.insert(target.clone());
+    }
+    if target.parent() == Some(cursor.current_dir().as_path()) {
+        cursor.select(&target)?;
+    }
+    Ok(None)
+}
+
+/// Creates an empty file, a directory along with missing parents, or a
+/// symlink or hardlink to the selected entry, named by `op` or on the bottom
+/// row, relative to the current directory. Lands on what was created. Returns
+/// the message, if any.
+fn create(cursor: &mut dyn Cursor, op: &OpType) -> anyhow::Result<Option<String>> {
+    let dir = cursor.current_dir();
+    let selected = cursor.selected();
+    let linking = matches!(op, OpType::Opsymlink(_) | OpType::Ophardlink(_));
+    if linking && selected.ends_with("..") {
+        return Ok(None);
+    }
+    if matches!(op, OpType::Ophardlink(_)) && selected.is_dir() {
+        return Ok(Some(String::from("can't hardlink a directory")));
+    }
+
+    let (question, initial, name) = match op {
+        OpType::Opnewfile(name) => ("new file: ", String::new(), name),
+        OpType::Opnewdir(name) => ("new directory: ", String::new(), name),
+        OpType::Opsymlink(name) => ("symlink: ", free_file_name(&selected), name),
+        OpType::Ophardlink(name) => ("hardlink: ", free_file_name(&selected), name),
+        _ => return Ok(None),
+    };
+    let name = match name {
+        Some(name) => Some(name.clone()),
+        None => prompt::input(question, &initial)?,
+    };
+    let name = match name {
+        Some(name) if !name.is_empty() => name,
+        _ => return Ok(None),
+    };
+    let path = dir.join(&name);
+    if path.symlink_metadata().is_ok() {
+        return Ok(Some(format!("{} exists", path.display())));
+    }
+
+    let result = match op {
+        OpType::Opnewfile(_) => fileops::create_file(&path),
+        OpType::Opnewdir(_) => std::fs::create_dir_all(&path).map_err(anyhow::Error::from),
+        OpType::Opsymlink(_) => {
+            // links next to their target stay valid when both are moved
+            let target = match selected.parent() == path.parent() {
+                true => PathBuf::from(selected.file_name().unwrap_or_default()),
+                false => selected.clone(),
+            };
+            std::os::unix::fs::symlink(target, &path).map_err(anyhow::Error::from)
         }
-        false => Command::new(prog)
-            .arg(path)
-            .spawn()
-            .expect("pager command failed to start"),
+        _ => std::fs::hard_link(&selected, &path).map_err(anyhow::Error::from),
     };
-    out.wait().expect("failed while waiting");
+    if let Err(e) = result {
+        return Ok(Some(format!("can't create {}: {:#}", path.display(), e)));
+    }
+
+    // for `a/b/c` that is `a`
+    let first = Path::new(&name).components().next().map(|c| dir.join(c));
+    for created in [Some(&path), first.as_ref()].into_iter().flatten() {
+        if let Some(parent) = created.parent() {
+            cursor.invalidate(parent)?;
+        }
+    }
+    match first {
+        Some(first) if first.parent() == Some(dir.as_path()) => cursor.select(&first)?,
+        _ => (),
+    }
+    Ok(None)
+}
+
+/// The name of `path` made unique the way pasting does, `name (1).ext`.
+fn free_file_name(path: &Path) -> String {
+    fileops::free_name(path)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Renames the marked paths, or everything in the current directory, by
+/// editing their names in the editor, one per line. Nothing is renamed when
+/// the edited names don't check out. Returns the message summing it up.
+fn bulk_rename(state: &mut State, cursor: &mut dyn Cursor) -> anyhow::Result<String> {
+    let dir = cursor.current_dir();
+    let mut paths = marked(state, cursor);
+    if paths.is_empty() {
+        paths = cursor.siblings(dir.clone())?;
+    }
+    if paths.is_empty() {
+        return Ok(String::from("nothing to rename"));
+    }
+    let lines: Vec<String> = paths.iter().map(|p| rename::line(p, &dir)).collect();
+    if let Some(path) = paths.iter().zip(&lines).find(|(_, l)| l.contains('\n')) {
+        return Ok(format!(
+            "can't rename {}, it has a newline",
+            path.0.display()
+        ));
+    }
+
+    let mut file = tempfile::Builder::new()
+        .prefix("noicer-rename-")
+        .suffix(".txt")
+        .tempfile()?;
+    file.write_all(lines.join("\n").as_bytes())?;
+    file.write_all(b"\n")?;
+    file.flush()?;
+    run_prog(&state.config.editor_command(file.path())?)?;
+    // editors may replace the file rather than write to it
+    let edited = std::fs::read_to_string(file.path())?;
+
+    let renames = match rename::plan(&paths, &lines, &edited, &dir) {
+        Ok(renames) if renames.is_empty() => return Ok(String::from("nothing renamed")),
+        Ok(renames) => renames,
+        Err(e) => return Ok(format!("nothing renamed: {:#}", e)),
+    };
+    let (done, result) = rename::apply(renames.clone());
+
+    for (from, to) in &renames {
+        for parent in [from.parent(), to.parent()].into_iter().flatten() {
+            cursor.invalidate(parent)?;
+        }
+    }
+
+    let mut message = format!("renamed {}", done);
+    match result {
+        Ok(()) => {
+            let marked: Vec<PathBuf> = renames
+                .iter()
+                .filter(|(from, _)| state.marks.remove(from))
+                .map(|(_, to)| to.clone())
+                .collect();
+            state.marks.extend(marked);
+        }
+        Err(e) => {
+            state.marks.retain(|m| m.symlink_metadata().is_ok());
+            message.push_str(&format!(", stopped: {:#}", e));
+        }
+    }
+    Ok(message)
+}
+
+/// Moves the trashed `paths` back to where they were deleted from. Only
+/// top level entries of the trash can be restored.
+fn restore(cursor: &mut dyn Cursor, paths: &[PathBuf]) -> anyhow::Result<String> {
+    let root = cursor.start_dir();
+    let paths: Vec<&PathBuf> = paths.iter().filter(|p| p.parent() == Some(&root)).collect();
+    let mut restored = Vec::new();
+    let mut result = Ok(());
+    for path in paths {
+        match trash::restore(path) {
+            Ok(target) => restored.push(target),
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    cursor.invalidate(&root)?;
+
+    let mut message = match restored.as_slice() {
+        [target] => format!("restored to {}", target.display()),
+        restored => format!("restored {}", restored.len()),
+    };
+    if let Err(e) = result {
+        message.push_str(&format!(", stopped: {:#}", e));
+    }
+    Ok(message)
+}
+
+/// Extracts `paths` to a destination asked for on the bottom row, which is
+/// relative to and defaults to `dir`. Returns the message summing it up.
+fn extract(cursor: &mut dyn Cursor, paths: &[PathBuf], dir: &Path) -> anyhow::Result<String> {
+    let dest = match prompt::input("extract to: ", &dir.to_string_lossy())? {
+        Some(dest) if !dest.is_empty() => dir.join(dest),
+        _ => return Ok(String::from("extract cancelled")),
+    };
+    let mut extractor = Extractor::new(cursor, |path: &Path| {
+        let question = format!("overwrite {}? [y]es [n]o [a]ll [q]uit", path.display());
+        Ok(match prompt::choose(&question, "ynaq")? {
+            Some('y') => Overwrite::Yes,
+            Some('a') => Overwrite::All,
+            Some('q') | None => Overwrite::Quit,
+            _ => Overwrite::No,
+        })
+    });
+    let result = extractor.extract(paths, &dest);
+    let mut message = format!("extracted {} to {}", extractor.extracted, dest.display());
+    if extractor.skipped > 0 {
+        message.push_str(&format!(", {} skipped", extractor.skipped));
+    }
+    if extractor.refused > 0 {
+        message.push_str(&format!(", {} unsafe refused", extractor.refused));
+    }
+    if let Err(e) = result {
+        message.push_str(&format!(", stopped: {:#}", e));
+    }
+    Ok(message)
+}
+
+/// Writes `paths` to an archive named on the bottom row, relative to the
+/// current directory, and selects it. Returns the message summing it up.
+fn compress(cursor: &mut dyn Cursor, paths: &[PathBuf]) -> anyhow::Result<String> {
+    let dir = cursor.current_dir();
+    let initial = match paths {
+        [path] => format!(
+            "{}.tar.gz",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        _ => String::from("archive.tar.gz"),
+    };
+    let target = match prompt::input("compress to: ", &initial)? {
+        Some(name) if !name.is_empty() => dir.join(name),
+        _ => return Ok(String::from("compress cancelled")),
+    };
+    if Format::from_name(&target).is_none() {
+        return Ok(format!(
+            "unknown archive format: {}, use .tar, .tar.gz, .tar.zst or .zip",
+            target.display()
+        ));
+    }
+    if target.symlink_metadata().is_ok() {
+        let question = format!("overwrite {}? [y/N]", target.display());
+        if prompt::choose(&question, "y")?.is_none() {
+            return Ok(String::from("compress cancelled"));
+        }
+    }
+
+    let result = compress::compress(paths, &target, |i, total, name| {
+        prompt::show(&format!("compressing {}/{}: {}", i, total, name.display()))
+    });
+    if let Some(parent) = target.parent() {
+        cursor.invalidate(parent)?;
+    }
+    match result {
+        Ok(()) => {
+            if target.parent() == Some(dir.as_path()) {
+                cursor.select(&target)?;
+            }
+            Ok(format!("created {}", target.display()))
+        }
+        Err(e) => Ok(format!("compress failed: {:#}", e)),
+    }
+}
+
+/// Writes the archive member `path` to a temp file named like it, so that
+/// archives without magic are still detected by their extension and rules
+/// match the copy like the member.
+fn temp_copy(cursor: &mut dyn Cursor, path: &Path) -> anyhow::Result<NamedTempFile> {
+    let content = cursor.read_file_content(path)?;
+    let name = path.file_name().unwrap_or_default();
+    let mut temp_file = tempfile::Builder::new().suffix(name).tempfile()?;
+    temp_file.write_all(&content)?;
+    Ok(temp_file)
+}
+
+/// Whether `path` is a directory inside an archive, which are listed with a
+/// trailing `/`.
+fn is_archive_dir(path: &Path) -> bool {
+    path.to_string_lossy().ends_with('/')
+}
+
+/// Whether `l` browses `file`, listed as `name`, as an archive. Zip-based
+/// formats like `.docx` or `.jar` are opened instead when a rule for opening
+/// them is configured.
+fn is_archive(config: &Config, name: &Path, file: &Path) -> bool {
+    !config.has_rule(Action::Open, name) && (compression::is_tar(file) || compression::is_zip(file))
+}
+
+/// Browses the archive `path`, read from `temp` when it is nested in another
+/// archive. An archive that can't be read is reported instead.
+fn enter_archive(state: &mut State, path: PathBuf, temp: Option<TempPath>) {
+    let file = temp.as_deref().unwrap_or(&path);
+    match open_archive(&path, file) {
+        Ok(cursor) => state.archives.push(Archive {
+            path,
+            temp,
+            cursor: Some(cursor),
+        }),
+        Err(e) => state.message = Some(format!("can't open {}: {:#}", path.display(), e)),
+    }
+}
+
+/// Creates the cursor for browsing the archive read from `file` under
+/// `path`.
+fn open_archive(path: &Path, file: &Path) -> anyhow::Result<Box<dyn Cursor>> {
+    let (tree, members): (ArchiveTree, Box<dyn Members>) = if compression::is_zip(file) {
+        let (tree, members) = ZipMembers::open(path, file)?;
+        (tree, Box::new(members))
+    } else {
+        let (tree, members) = TarMembers::open(path, file)?;
+        (tree, Box::new(members))
+    };
+    Ok(Box::new(ArchiveCursor::new(path, tree, members)))
+}
+
+/// Runs the program configured for `action` on `file`, which is listed as
+/// `name`. Compressed files other than tarballs are decompressed to a temp
+/// file named without the compression extension and shown in the pager, or
+/// piped to it when they are larger than `stream_above`.
+fn run_action(config: &Config, action: Action, name: &Path, file: &Path) -> anyhow::Result<()> {
+    // a file that can't be read is left to the program to report
+    let compression = match file.is_file() {
+        true => Compression::detect(file).unwrap_or(Compression::None),
+        false => Compression::None,
+    };
+    if compression == Compression::None || compression::is_tar(file) {
+        return run_prog(&config.command(action, name, file)?);
+    }
+
+    // edits to a decompressed copy would be lost, so it is only viewed
+    let action = match action {
+        Action::Edit => Action::Page,
+        action => action,
+    };
+    let stem = name.file_stem().unwrap_or_default();
+    let name = name.with_file_name(stem);
+    let mut decoder = compression.decoder(File::open(file)?)?;
+    if config
+        .stream_above
+        .is_some_and(|limit| file.metadata().is_ok_and(|m| m.len() > limit))
+    {
+        return run_prog_piped(&config.command(action, &name, Path::new("-"))?, decoder);
+    }
+
+    let dir = tempfile::tempdir()?;
+    let temp = dir.path().join(stem);
+    io::copy(&mut decoder, &mut File::create(&temp)?)?;
+    run_prog(&config.command(action, &name, &temp)?)
+}
+
+pub fn run_prog(args: &[String]) -> anyhow::Result<()> {
+    let (prog, args) = args.split_first().ok_or(anyhow::anyhow!("empty command"))?;
+    let mut out = Command::new(prog)
+        .args(args)
+        .spawn()
+        .with_context(|| format!("{} failed to start", prog))?;
+    out.wait()
+        .with_context(|| format!("failed waiting for {}", prog))?;
     Ok(())
 }
 
-fn create_status_bar(cursor: &dyn Cursor, _engine: &Engine) -> String {
+/// Runs a one-off shell command outside the alternate screen, so its output
+/// stays readable until a key is pressed.
+fn run_shell(args: &[String]) -> anyhow::Result<()> {
+    let mut w = io::stdout();
+    execute!(w, terminal::LeaveAlternateScreen, crossterm::cursor::Show)?;
+    terminal::disable_raw_mode()?;
+    let result = run_prog(args);
+    print!("\npress any key to continue");
+    w.flush()?;
+    terminal::enable_raw_mode()?;
+    loop {
+        if let Event::Key(_) = event::read()? {
+            break;
+        }
+    }
+    execute!(w, terminal::EnterAlternateScreen)?;
+    result
+}
+
+/// Like `run_prog`, with `input` copied to the program's stdin.
+pub fn run_prog_piped(args: &[String], mut input: impl Read) -> anyhow::Result<()> {
+    let (prog, args) = args.split_first().ok_or(anyhow::anyhow!("empty command"))?;
+    let mut out = Command::new(prog)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("{} failed to start", prog))?;
+    if let Some(mut stdin) = out.stdin.take() {
+        match io::copy(&mut input, &mut stdin) {
+            // the pager was quit before reading everything
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+            result => {
+                result?;
+            }
+        }
+    }
+    out.wait()
+        .with_context(|| format!("failed waiting for {}", prog))?;
+    Ok(())
+}
+
+fn create_status_bar(
+    cursor: &mut dyn Cursor,
+    engine: &Engine,
+    marked: usize,
+) -> anyhow::Result<String> {
     let sorting = match cursor.sort() {
         Sort::Dir => "D",
         Sort::Name => "N",
@@ -240,5 +1209,20 @@ fn create_status_bar(cursor: &dyn Cursor, _engine: &Engine) -> String {
     } else {
         selected_name.to_string()
     };
-    format!(" Selected: {} | Sorting: {}", selected_name, sorting)
+    let mut status = format!(" Selected: {} | Sorting: {}", selected_name, sorting);
+    if !engine.last_search().is_empty() {
+        let (index, total) = cursor.match_position(engine.last_search())?;
+        match index {
+            Some(index) => status.push_str(&format!(" | match {}/{}", index, total)),
+            None => status.push_str(&format!(" | match -/{}", total)),
+        }
+    }
+    // marks elsewhere don't count, nothing here acts on them
+    if marked > 0 {
+        status.push_str(&format!(" | marked: {}", marked));
+    }
+    if let Some(count) = engine.count() {
+        status.push_str(&format!(" | count: {}", count));
+    }
+    Ok(status)
 }
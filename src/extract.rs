@@ -0,0 +1,166 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, Permissions},
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Context;
+
+use crate::cursor::{Cursor, Entry, Link};
+
+/// What to do with an entry whose target already exists.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Overwrite {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+/// Writes archive entries and the subtrees below them to a directory.
+/// Unsafe members are refused and existing files are only replaced once
+/// `ask` allows it.
+pub(crate) struct Extractor<'a, F: FnMut(&Path) -> anyhow::Result<Overwrite>> {
+    cursor: &'a mut dyn Cursor,
+    ask: F,
+    overwrite_all: bool,
+    quit: bool,
+    // where each member written so far ended up, for hard links to it
+    written: HashMap<PathBuf, PathBuf>,
+    pub extracted: usize,
+    pub skipped: usize,
+    pub refused: usize,
+}
+
+impl<'a, F: FnMut(&Path) -> anyhow::Result<Overwrite>> Extractor<'a, F> {
+    pub fn new(cursor: &'a mut dyn Cursor, ask: F) -> Self {
+        Self {
+            cursor,
+            ask,
+            overwrite_all: false,
+            quit: false,
+            written: HashMap::new(),
+            extracted: 0,
+            skipped: 0,
+            refused: 0,
+        }
+    }
+
+    /// Extracts every path in `paths` into `dest` under its own name.
+    pub fn extract(&mut self, paths: &[PathBuf], dest: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(dest).with_context(|| format!("can't create {}", dest.display()))?;
+        for path in paths {
+            let parent = path.parent().unwrap_or(Path::new(""));
+            let entry = self
+                .cursor
+                .entries(parent)?
+                .into_iter()
+                .find(|e| &e.path == path)
+                .ok_or_else(|| anyhow::anyhow!("{} not found in archive", path.display()))?;
+            let name = path.file_name().unwrap_or_default();
+            self.extract_entry(&entry, &dest.join(name))?;
+        }
+        Ok(())
+    }
+
+    fn extract_entry(&mut self, entry: &Entry, target: &Path) -> anyhow::Result<()> {
+        if self.quit {
+            return Ok(());
+        }
+        if self.cursor.is_unsafe(&entry.path) {
+            self.refused += 1;
+            return Ok(());
+        }
+
+        if entry.is_dir {
+            // a symlink extracted in its place would lead out of `dest`
+            if target.symlink_metadata().is_ok_and(|m| m.is_symlink()) {
+                self.refused += 1;
+                return Ok(());
+            }
+            if !target.is_dir() {
+                fs::create_dir(target)
+                    .with_context(|| format!("can't create {}", target.display()))?;
+            }
+            for child in self.cursor.entries(&entry.path)? {
+                let name = child.path.file_name().unwrap_or_default().to_os_string();
+                self.extract_entry(&child, &target.join(name))?;
+            }
+            // after the children, a read-only directory can't be written to
+            // and creating them would update the mtime
+            set_metadata(entry, target)?;
+            return Ok(());
+        }
+
+        if target.symlink_metadata().is_ok() && !self.overwrite_all {
+            match (self.ask)(target)? {
+                Overwrite::Yes => (),
+                Overwrite::All => self.overwrite_all = true,
+                Overwrite::No => {
+                    self.skipped += 1;
+                    return Ok(());
+                }
+                Overwrite::Quit => {
+                    self.quit = true;
+                    return Ok(());
+                }
+            }
+        }
+        let existing = match &entry.link {
+            Some(Link::Hard(linked)) => self.written.get(linked).cloned(),
+            _ => None,
+        };
+        match (&entry.link, existing) {
+            (Some(Link::Symlink(link)), _) => {
+                replace(target)?;
+                symlink(link, target)
+                    .with_context(|| format!("can't create {}", target.display()))?;
+            }
+            (Some(Link::Hard(_)), Some(existing)) => {
+                replace(target)?;
+                fs::hard_link(existing, target)
+                    .with_context(|| format!("can't create {}", target.display()))?;
+            }
+            // a hard link to a member that isn't extracted gets its content
+            (link, _) => {
+                let source = match link {
+                    Some(Link::Hard(linked)) => linked,
+                    _ => &entry.path,
+                };
+                let content = self.cursor.read_file_content(source)?;
+                replace(target)?;
+                fs::write(target, content)
+                    .with_context(|| format!("can't write {}", target.display()))?;
+                set_metadata(entry, target)?;
+                self.written
+                    .insert(entry.path.clone(), target.to_path_buf());
+            }
+        }
+        self.extracted += 1;
+        Ok(())
+    }
+}
+
+/// Removes what is at `target` rather than writing through it, it may be a
+/// symlink.
+fn replace(target: &Path) -> anyhow::Result<()> {
+    if target.symlink_metadata().is_ok() {
+        fs::remove_file(target).with_context(|| format!("can't replace {}", target.display()))?;
+    }
+    Ok(())
+}
+
+/// Applies the mtime and permission bits from the archive to `target`.
+/// Directories that were only implied by their members have no mtime to
+/// restore. Like `tar` run by a regular user, setuid and friends are dropped.
+fn set_metadata(entry: &Entry, target: &Path) -> anyhow::Result<()> {
+    if entry.mtime != UNIX_EPOCH {
+        File::open(target)?.set_modified(entry.mtime)?;
+    }
+    if entry.mode & 0o777 != 0 {
+        fs::set_permissions(target, Permissions::from_mode(entry.mode & 0o777))?;
+    }
+    Ok(())
+}
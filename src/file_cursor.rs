@@ -1,19 +1,35 @@
 use std::{
     collections::HashMap,
-    ffi::OsStr,
+    ops::Range,
     path::{Path, PathBuf},
-    time::UNIX_EPOCH,
+    time::SystemTime,
 };
 
 use anyhow::Result;
 
-use crate::cursor::{Cursor, Sort};
+use crate::cursor::{Cursor, Entry, Settings, Sort};
+use crate::matcher::Matcher;
+
+/// A cached `read_dir` of one directory. `view` is the sorted and filtered
+/// listing the cursor moves over and is rebuilt from `entries` without
+/// touching the disk when the sort, hidden or filter settings change.
+struct Listing {
+    mtime: Option<SystemTime>,
+    entries: Vec<Entry>,
+    view: Option<View>,
+}
+
+struct View {
+    paths: Vec<PathBuf>,
+    index: HashMap<PathBuf, usize>,
+}
 
 pub struct FileCursor {
-    hide: bool,
-    casing: bool,
-    sort: Sort,
+    settings: Settings,
     paths: HashMap<PathBuf, PathBuf>,
+    filters: HashMap<PathBuf, String>,
+    listings: HashMap<PathBuf, Listing>,
+    watched: Option<PathBuf>,
     start_cwd: Option<PathBuf>,
     selected: PathBuf,
 }
@@ -21,46 +37,123 @@ pub struct FileCursor {
 impl FileCursor {
     pub fn new() -> Self {
         Self {
-            hide: true,
-            casing: false,
-            sort: Sort::Name,
+            settings: Settings::new(),
             paths: HashMap::new(),
+            filters: HashMap::new(),
+            listings: HashMap::new(),
+            watched: None,
             start_cwd: None,
             selected: PathBuf::new(),
         }
     }
+
+    /// Sets the directory with a live inotify watch. Its listing is only
+    /// read again when invalidated, the others whenever their mtime changed.
+    pub fn set_watched(&mut self, dir: Option<&Path>) {
+        self.watched = dir.map(Path::to_path_buf);
+    }
+
+    /// Returns the cached listing of `dir`, reading the directory again when
+    /// it isn't cached yet or, unless it is watched, its mtime changed since
+    /// the last read.
+    fn view(&mut self, dir: &Path) -> Result<&View> {
+        let watched = self.watched.as_deref() == Some(dir);
+        let mtime = match watched && self.listings.contains_key(dir) {
+            true => None,
+            false => std::fs::metadata(dir).and_then(|m| m.modified()).ok(),
+        };
+        let stale = match self.listings.get(dir) {
+            Some(_) if watched => false,
+            Some(listing) => mtime.is_none() || listing.mtime != mtime,
+            None => true,
+        };
+        if stale {
+            let mut entries = Vec::new();
+            for entry in std::fs::read_dir(dir)? {
+                entries.push(Entry::from_path(entry?.path()));
+            }
+            self.listings.insert(
+                dir.to_path_buf(),
+                Listing {
+                    mtime,
+                    entries,
+                    view: None,
+                },
+            );
+        }
+
+        if self.listings.get(dir).is_some_and(|l| l.view.is_none()) {
+            let view = self.build_view(dir);
+            if let Some(listing) = self.listings.get_mut(dir) {
+                listing.view = Some(view);
+            }
+        }
+
+        self.listings
+            .get(dir)
+            .and_then(|l| l.view.as_ref())
+            .ok_or(anyhow::anyhow!("no listing for {}", dir.display()))
+    }
+
+    fn build_view(&self, dir: &Path) -> View {
+        let entries = self.listings.get(dir).map(|l| l.entries.as_slice());
+        let filter = self.filters.get(dir).map(String::as_str);
+        let paths = self.settings.view(entries.unwrap_or_default(), filter);
+        let index = paths
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.clone(), i))
+            .collect();
+        View { paths, index }
+    }
+
+    fn invalidate_views(&mut self) {
+        for listing in self.listings.values_mut() {
+            listing.view = None;
+        }
+    }
 }
 
 impl Cursor for FileCursor {
     fn init(&mut self, cwd: &Path) -> Result<()> {
         self.start_cwd = Some(cwd.to_path_buf().clone());
         self.paths = HashMap::new();
-        self.selected = if let Some(p) = self.siblings(std::env::current_dir()?)?.first() {
+        self.selected = if let Some(p) = self.siblings(cwd.to_path_buf())?.first() {
             p.clone()
         } else {
-            std::env::current_dir()?.join(PathBuf::from(".."))
+            cwd.join(PathBuf::from(".."))
         };
         Ok(())
     }
 
+    fn read_file_content(&mut self, path: &Path) -> Result<Vec<u8>> {
+        Ok(std::fs::read(path)?)
+    }
+
     fn move_down(&mut self, n: i32) -> Result<()> {
-        let siblings = self.siblings(self.current_dir())?;
         let pos = self.pos()?;
-        if pos < siblings.len() as i32 - n {
-            self.selected = siblings[(pos + n) as usize].clone();
+        let siblings = &self.view(&self.current_dir())?.paths;
+        let next = if pos < siblings.len() as i32 - n {
+            siblings.get((pos + n) as usize).cloned()
         } else {
-            self.selected = siblings.last().unwrap_or(&self.selected).clone()
+            siblings.last().cloned()
+        };
+        if let Some(p) = next {
+            self.selected = p;
         }
         Ok(())
     }
 
     fn move_up(&mut self, n: i32) -> Result<()> {
-        let siblings = self.siblings(self.current_dir())?;
         let pos = self.pos()?;
-        if pos >= n {
-            self.selected = siblings[(pos - n) as usize].clone();
+        let siblings = &self.view(&self.current_dir())?.paths;
+        let next = if pos >= n {
+            siblings.get((pos - n) as usize).cloned()
         } else {
-            self.selected = siblings.first().unwrap_or(&self.selected).clone()
+            siblings.first().cloned()
+        };
+        if let Some(p) = next {
+            self.selected = p;
         }
         Ok(())
     }
@@ -96,45 +189,75 @@ impl Cursor for FileCursor {
         Ok(())
     }
 
+    fn move_to(&mut self, dir: &Path) -> Result<()> {
+        if !dir.is_dir() {
+            anyhow::bail!("{} is not a directory", dir.display());
+        }
+        self.paths.insert(self.current_dir(), self.selected());
+        self.selected = match self.paths.get(dir) {
+            Some(p) => p.clone(),
+            None => match self.siblings(dir.to_path_buf())?.first() {
+                Some(p) => p.clone(),
+                None => dir.join(".."),
+            },
+        };
+        std::env::set_current_dir(dir)?;
+        Ok(())
+    }
+
     fn move_bottom(&mut self) -> Result<()> {
-        let siblings = self.siblings(self.current_dir())?;
-        self.selected = siblings[siblings.len() - 1].clone();
+        let last = self.view(&self.current_dir())?.paths.last().cloned();
+        if let Some(p) = last {
+            self.selected = p;
+        }
         Ok(())
     }
 
     fn move_top(&mut self) -> Result<()> {
-        let siblings = self.siblings(self.current_dir())?;
-        self.selected = siblings[0].clone();
+        let first = self.view(&self.current_dir())?.paths.first().cloned();
+        if let Some(p) = first {
+            self.selected = p;
+        }
         Ok(())
     }
 
     fn toggle_hidden_files(&mut self) -> Result<()> {
-        self.hide = !self.hide;
+        self.settings.hide = !self.settings.hide;
+        self.invalidate_views();
         Ok(())
     }
 
+    fn hides_hidden_files(&self) -> bool {
+        self.settings.hide
+    }
+
     fn toggle_case_sensitivity(&mut self) -> Result<()> {
-        self.casing = !self.casing;
+        self.settings.casing = !self.settings.casing;
+        self.invalidate_views();
         Ok(())
     }
 
     fn sort_dir(&mut self) -> Result<()> {
-        self.sort = Sort::Dir;
+        self.settings.sort = Sort::Dir;
+        self.invalidate_views();
         Ok(())
     }
 
     fn sort_name(&mut self) -> Result<()> {
-        self.sort = Sort::Name;
+        self.settings.sort = Sort::Name;
+        self.invalidate_views();
         Ok(())
     }
 
     fn sort_size(&mut self) -> Result<()> {
-        self.sort = Sort::Size;
+        self.settings.sort = Sort::Size;
+        self.invalidate_views();
         Ok(())
     }
 
     fn sort_time(&mut self) -> Result<()> {
-        self.sort = Sort::Time;
+        self.settings.sort = Sort::Time;
+        self.invalidate_views();
         Ok(())
     }
 
@@ -149,21 +272,49 @@ impl Cursor for FileCursor {
     }
 
     fn matching_siblings(&mut self, pattern: &str) -> Result<Vec<PathBuf>> {
-        let siblings = self.siblings(self.current_dir())?;
+        let matcher = match Matcher::new(pattern) {
+            Ok(matcher) => matcher,
+            Err(_) => return Ok(Vec::new()),
+        };
+        let siblings = &self.view(&self.current_dir())?.paths;
         let mut matches = Vec::new();
         for sibling in siblings {
-            if sibling
-                .to_str()
-                .unwrap_or("")
-                .to_lowercase()
-                .contains(&pattern.to_lowercase())
-            {
-                matches.push(sibling);
+            if matcher.is_match(sibling) {
+                matches.push(sibling.clone());
             }
         }
         Ok(matches)
     }
 
+    fn filter(&self) -> Option<String> {
+        self.filters.get(&self.current_dir()).cloned()
+    }
+
+    fn set_filter(&mut self, pattern: &str) -> Result<()> {
+        let dir = self.current_dir();
+        if pattern.is_empty() {
+            self.filters.remove(&dir);
+        } else {
+            self.filters.insert(dir.clone(), pattern.to_string());
+        }
+        if let Some(listing) = self.listings.get_mut(&dir) {
+            listing.view = None;
+        }
+        let siblings = self.siblings(dir.clone())?;
+        if !siblings.contains(&self.selected) {
+            self.selected = match siblings.first() {
+                Some(p) => p.clone(),
+                None => dir.join(".."),
+            };
+        }
+        Ok(())
+    }
+
+    fn select(&mut self, path: &Path) -> Result<()> {
+        self.selected = path.to_path_buf();
+        Ok(())
+    }
+
     fn selected(&self) -> PathBuf {
         self.selected.clone()
     }
@@ -183,104 +334,75 @@ impl Cursor for FileCursor {
             .to_path_buf()
     }
 
-    fn current_siblings(&mut self) -> Result<Option<Vec<PathBuf>>> {
-        let siblings = self.siblings(self.current_dir())?;
-        if !siblings.is_empty() {
-            Ok(Some(siblings))
-        } else {
-            Ok(None)
-        }
-    }
-
-    fn siblings(&mut self, path: PathBuf) -> Result<Vec<PathBuf>> {
-        let mut siblings = Vec::new();
-        for entry in std::fs::read_dir(path)? {
-            let path = &entry?.path();
-            if self.hide && self.hidden(path) {
-                continue;
-            } else {
-                siblings.push(path.clone())
-            }
-        }
-        match self.sort {
-            Sort::Dir => self.sort_by_dir(&mut siblings),
-            Sort::Name => self.sort_by_name(&mut siblings),
-            Sort::Size => self.sort_by_size(&mut siblings),
-            Sort::Time => self.sort_by_time(&mut siblings),
-        }
-        match self.casing {
-            true => self.sort_by_casing(&mut siblings),
-            false => (),
-        }
-        Ok(siblings)
-    }
-
-    fn hidden(&self, path: &Path) -> bool {
-        path.file_name()
-            .unwrap_or(OsStr::new(""))
-            .to_str()
-            .unwrap_or("")
-            .starts_with('.')
+    fn current_len(&mut self) -> Result<usize> {
+        Ok(self.view(&self.current_dir())?.paths.len())
     }
 
-    fn sort(&self) -> Sort {
-        self.sort.clone()
+    fn current_window(&mut self, range: Range<usize>) -> Result<Vec<PathBuf>> {
+        let paths = &self.view(&self.current_dir())?.paths;
+        let end = range.end.min(paths.len());
+        Ok(paths[range.start.min(end)..end].to_vec())
     }
 
-    fn sort_by_casing(&self, siblings: &mut [PathBuf]) {
-        if self.casing {
-            siblings.sort_by(|a, b| {
-                a.to_str()
-                    .unwrap_or("")
-                    .to_lowercase()
-                    .cmp(&b.to_str().unwrap_or("").to_lowercase())
-            });
-        }
+    fn siblings(&mut self, path: PathBuf) -> Result<Vec<PathBuf>> {
+        Ok(self.view(&path)?.paths.clone())
     }
 
-    fn sort_by_dir(&self, siblings: &mut [PathBuf]) {
-        siblings.sort_by_key(|b| std::cmp::Reverse(b.is_dir()));
+    fn entries(&mut self, dir: &Path) -> Result<Vec<Entry>> {
+        self.view(dir)?;
+        Ok(self
+            .listings
+            .get(dir)
+            .map(|l| l.entries.clone())
+            .unwrap_or_default())
     }
 
-    fn sort_by_name(&self, siblings: &mut [PathBuf]) {
-        siblings.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    fn refresh(&mut self) -> Result<()> {
+        self.listings.clear();
+        Ok(())
     }
 
-    fn sort_by_time(&self, siblings: &mut [PathBuf]) {
-        siblings.sort_by(|a, b| {
-            let a_mtime = a
-                .metadata()
-                .map_or(UNIX_EPOCH, |m| m.modified().unwrap_or(UNIX_EPOCH));
-            let b_mtime = b
-                .metadata()
-                .map_or(UNIX_EPOCH, |m| m.modified().unwrap_or(UNIX_EPOCH));
-            a_mtime.cmp(&b_mtime)
-        });
+    fn invalidate(&mut self, dir: &Path) -> Result<()> {
+        // the current directory was removed, go to the nearest one left
+        if dir == self.current_dir() && !dir.is_dir() {
+            self.listings.remove(dir);
+            if let Some(ancestor) = dir.ancestors().find(|a| a.is_dir()) {
+                let ancestor = ancestor.to_path_buf();
+                self.selected = match self.siblings(ancestor.clone())?.first() {
+                    Some(p) => p.clone(),
+                    None => ancestor.join(".."),
+                };
+                std::env::set_current_dir(ancestor)?;
+            }
+            return Ok(());
+        }
+        let pos = self.pos()?;
+        self.listings.remove(dir);
+        if dir != self.current_dir() {
+            return Ok(());
+        }
+        // keep the selection on the same name, or on the same row if the
+        // selected entry is gone
+        let selected = self.selected.clone();
+        let view = self.view(dir)?;
+        if !view.index.contains_key(&selected) {
+            let last = view.paths.len() as i32 - 1;
+            self.selected = match view.paths.get(pos.min(last).max(0) as usize) {
+                Some(p) => p.clone(),
+                None => dir.join(".."),
+            };
+        }
+        Ok(())
     }
 
-    fn sort_by_size(&self, siblings: &mut [PathBuf]) {
-        siblings.sort_by(|a, b| {
-            let a_len = a.metadata().map(|m| m.len()).unwrap_or(0);
-            let b_len = b.metadata().map(|m| m.len()).unwrap_or(0);
-            a_len.cmp(&b_len)
-        });
+    fn sort(&self) -> Sort {
+        self.settings.sort.clone()
     }
 
     fn pos(&mut self) -> Result<i32> {
-        let pos = self
-            .siblings(self.current_dir())?
-            .iter()
-            .position(|p| {
-                // println!("{:?}", p);
-                // println!("{:?}", &self.selected);
-                p == &self.selected
-            })
-            .unwrap_or(0) as i32;
-        Ok(pos)
-    }
-
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-        self
+        let selected = self.selected.clone();
+        let view = self.view(&self.current_dir())?;
+        Ok(view.index.get(&selected).copied().unwrap_or(0) as i32)
     }
 }
 
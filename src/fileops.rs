@@ -0,0 +1,173 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+/// What to do when the target of a copy or move already exists.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Conflict {
+    Overwrite,
+    Skip,
+    Rename,
+    Quit,
+}
+
+/// Copies or moves paths into a directory. Existing targets are resolved
+/// through `ask`, which also says whether to resolve all further conflicts
+/// the same way.
+pub struct Transfer<F: FnMut(&Path) -> anyhow::Result<(Conflict, bool)>> {
+    ask: F,
+    always: Option<Conflict>,
+    pub done: Vec<PathBuf>,
+    pub skipped: usize,
+}
+
+impl<F: FnMut(&Path) -> anyhow::Result<(Conflict, bool)>> Transfer<F> {
+    pub fn new(ask: F) -> Self {
+        Self {
+            ask,
+            always: None,
+            done: Vec::new(),
+            skipped: 0,
+        }
+    }
+
+    /// Copies, or moves when `cut` is set, every path in `paths` into `dir`
+    /// under its own name. `progress` is called before each path.
+    pub fn run(
+        &mut self,
+        paths: &[PathBuf],
+        dir: &Path,
+        cut: bool,
+        mut progress: impl FnMut(usize, &Path) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        for (i, path) in paths.iter().enumerate() {
+            progress(i, path)?;
+            let name = path.file_name().unwrap_or_default();
+            let mut target = dir.join(name);
+            if target.starts_with(path) && target != *path {
+                anyhow::bail!("can't paste {} into itself", path.display());
+            }
+
+            if target.symlink_metadata().is_ok() {
+                let conflict = match self.always {
+                    Some(conflict) => conflict,
+                    None => {
+                        let (conflict, all) = (self.ask)(&target)?;
+                        if all {
+                            self.always = Some(conflict);
+                        }
+                        conflict
+                    }
+                };
+                match conflict {
+                    // replacing a path with itself or with something it
+                    // contains would delete the source
+                    Conflict::Overwrite if path.starts_with(&target) => {
+                        self.skipped += 1;
+                        continue;
+                    }
+                    Conflict::Overwrite => remove(&target)?,
+                    Conflict::Skip => {
+                        self.skipped += 1;
+                        continue;
+                    }
+                    Conflict::Rename => target = free_name(&target),
+                    Conflict::Quit => return Ok(()),
+                }
+            }
+
+            match cut {
+                true => move_path(path, &target)?,
+                false => copy(path, &target)?,
+            }
+            self.done.push(target);
+        }
+        Ok(())
+    }
+}
+
+/// Copies `from` to `to`, directories recursively. Symlinks are copied as
+/// links rather than followed.
+pub fn copy(from: &Path, to: &Path) -> anyhow::Result<()> {
+    let metadata = from.symlink_metadata()?;
+    if metadata.is_symlink() {
+        symlink(fs::read_link(from)?, to)
+            .with_context(|| format!("can't create {}", to.display()))?;
+    } else if metadata.is_dir() {
+        fs::create_dir(to).with_context(|| format!("can't create {}", to.display()))?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())?;
+    } else {
+        fs::copy(from, to).with_context(|| format!("can't copy to {}", to.display()))?;
+    }
+    Ok(())
+}
+
+/// Moves `from` to `to`. `rename` only works within a filesystem, across
+/// filesystems the move is a copy followed by a delete.
+pub fn move_path(from: &Path, to: &Path) -> anyhow::Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy(from, to)?;
+            remove(from)
+        }
+        Err(e) => Err(e).with_context(|| format!("can't move to {}", to.display())),
+    }
+}
+
+/// Creates an empty file at `path`, failing when something is there already.
+pub fn create_file(path: &Path) -> anyhow::Result<()> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .with_context(|| format!("can't create {}", path.display()))?;
+    Ok(())
+}
+
+/// Deletes `path`, directories with everything in them.
+pub fn remove(path: &Path) -> anyhow::Result<()> {
+    let result = match path.symlink_metadata()?.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    };
+    result.with_context(|| format!("can't remove {}", path.display()))
+}
+
+/// The first of `name (1).ext`, `name (2).ext`, ... that doesn't exist yet.
+pub fn free_name(path: &Path) -> PathBuf {
+    (1..)
+        .map(|n| numbered(path, n))
+        .find(|candidate| candidate.symlink_metadata().is_err())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// `path` with ` (n)` added to its name, before the extension. The extension
+/// starts at the first dot, so `a.tar.gz` becomes `a (1).tar.gz`, and a
+/// leading dot is part of the name.
+pub fn numbered(path: &Path, n: usize) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (stem, ext) = name.split_at(stem_len(&name));
+    let mut numbered = OsString::from(format!("{} ({})", stem, n));
+    numbered.push(ext);
+    path.with_file_name(numbered)
+}
+
+/// The length in bytes of `name` up to its extension, which starts at the
+/// first dot that isn't leading.
+pub fn stem_len(name: &str) -> usize {
+    name.char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '.')
+        .map(|(i, _)| i)
+        .unwrap_or(name.len())
+}
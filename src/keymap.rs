@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::engine::OpType;
+
+/// A key press with its modifiers. Shift is part of the character for
+/// character keys, so `D` and shift-d are the same key.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        let modifiers = match code {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Key { code, modifiers }
+    }
+
+    /// The digit this key types, when it is a plain digit key.
+    pub fn digit(&self) -> Option<char> {
+        match self.code {
+            KeyCode::Char(c) if c.is_ascii_digit() && self.modifiers.is_empty() => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(ke: KeyEvent) -> Key {
+        Key::new(ke.code, ke.modifiers)
+    }
+}
+
+/// Parses a key sequence written the way vim does, like `gg`, `<C-d>`,
+/// `<PageDown>`, `<A-Left>` or `<F5>`. `<lt>` is `<` and `<Space>` the
+/// space bar.
+pub fn parse_keys(keys: &str) -> anyhow::Result<Vec<Key>> {
+    let mut parsed = Vec::new();
+    let mut rest = keys;
+    while let Some(c) = rest.chars().next() {
+        let special = match c {
+            '<' => rest[1..].find('>').map(|end| &rest[1..end + 1]),
+            _ => None,
+        };
+        match special {
+            Some(name) => {
+                parsed.push(parse_key(name)?);
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                parsed.push(Key::new(KeyCode::Char(c), KeyModifiers::NONE));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if parsed.is_empty() {
+        anyhow::bail!("empty key sequence");
+    }
+    Ok(parsed)
+}
+
+/// Parses the part of a special key between `<` and `>`.
+fn parse_key(name: &str) -> anyhow::Result<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut key = name;
+    // a trailing `-` is the minus key, as in `<C-->`
+    while let Some((modifier, rest)) = key.split_once('-').filter(|(_, rest)| !rest.is_empty()) {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "c" => KeyModifiers::CONTROL,
+            "a" | "m" => KeyModifiers::ALT,
+            "s" => KeyModifiers::SHIFT,
+            _ => anyhow::bail!("unknown modifier in <{}>", name),
+        };
+        key = rest;
+    }
+
+    let mut chars = key.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+            KeyCode::Char(c.to_ascii_uppercase())
+        }
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match key.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "lt" => KeyCode::Char('<'),
+            "gt" => KeyCode::Char('>'),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "enter" | "cr" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "bs" | "backspace" => KeyCode::Backspace,
+            "del" | "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            lower => match lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n) if (1..=24).contains(&n) => KeyCode::F(n),
+                _ => anyhow::bail!("unknown key <{}>", name),
+            },
+        },
+    };
+    let modifiers = match code {
+        KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+        _ => modifiers,
+    };
+    Ok(Key::new(code, modifiers))
+}
+
+/// The built-in bindings, which the `[keys]` table of the config file adds
+/// to or overrides.
+fn defaults() -> Vec<(&'static str, OpType)> {
+    vec![
+        ("q", OpType::Opq),
+        ("G", OpType::OpG),
+        ("gg", OpType::Opgg),
+        ("H", OpType::OpH),
+        ("M", OpType::OpM),
+        ("L", OpType::OpL),
+        ("<C-d>", OpType::Opctrld),
+        ("<C-u>", OpType::Opctrlu),
+        ("<C-f>", OpType::Opctrlf),
+        ("<C-b>", OpType::Opctrlb),
+        ("<C-l>", OpType::Opctrll),
+        ("j", OpType::Opj(1)),
+        ("k", OpType::Opk(1)),
+        ("h", OpType::Oph),
+        ("l", OpType::Opl),
+        ("<Down>", OpType::Opj(1)),
+        ("<Up>", OpType::Opk(1)),
+        ("<Left>", OpType::Oph),
+        ("<Right>", OpType::Opl),
+        ("<PageDown>", OpType::Opctrlf),
+        ("<PageUp>", OpType::Opctrlb),
+        ("<Home>", OpType::Opgg),
+        ("<End>", OpType::OpG),
+        (".", OpType::Opdot),
+        ("~", OpType::Opcasing),
+        ("od", OpType::Opsortdir),
+        ("on", OpType::Opsortname),
+        ("os", OpType::Opsortsize),
+        ("ot", OpType::Opsorttime),
+        // the original single keys, where they are still free
+        ("s", OpType::Opsortsize),
+        ("t", OpType::Opsorttime),
+        ("yy", OpType::Opyank),
+        ("dd", OpType::Opcut),
+        ("dD", OpType::Opdelete),
+        ("D", OpType::Optrash),
+        ("T", OpType::Optrashview),
+        ("r", OpType::Oprename(None)),
+        ("R", OpType::OpR),
+        ("mf", OpType::Opnewfile(None)),
+        ("md", OpType::Opnewdir(None)),
+        ("ms", OpType::Opsymlink(None)),
+        ("mh", OpType::Ophardlink(None)),
+        ("n", OpType::Opn),
+        ("N", OpType::OpN),
+        ("/", OpType::Opslash),
+        (":", OpType::Opcolon),
+        ("p", OpType::Oppaste),
+        ("P", OpType::Oppage),
+        ("e", OpType::Opedit),
+        (" ", OpType::Opmark),
+        ("V", OpType::Opmarkall),
+        ("v", OpType::Opinvertmarks),
+        ("U", OpType::Opclearmarks),
+        ("x", OpType::Opextract),
+        ("C", OpType::Opcompress),
+        ("!", OpType::Opbang),
+        ("?", OpType::Opquestion),
+    ]
+}
+
+/// What a key sequence leads to in the keymap.
+pub enum Lookup<'a> {
+    /// Nothing is bound to the sequence or anything starting with it.
+    Unbound,
+    /// The sequence is bound and nothing longer starts with it.
+    Bound(&'a OpType),
+    /// Longer sequences start with this one, which may be bound itself.
+    Prefix(Option<&'a OpType>),
+}
+
+#[derive(Default)]
+struct Node {
+    op: Option<OpType>,
+    children: HashMap<Key, Node>,
+}
+
+/// Key sequences and what they are bound to, kept as a prefix trie so each
+/// key press narrows down the candidates.
+pub struct Keymap {
+    root: Node,
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        let mut keymap = Keymap {
+            root: Node::default(),
+        };
+        for (keys, op) in defaults() {
+            let keys = parse_keys(keys).expect("invalid default key binding");
+            keymap.bind(&keys, Some(op));
+        }
+        keymap
+    }
+
+    /// Binds `keys` to `op`, or unbinds them when `op` is `None`.
+    pub fn bind(&mut self, keys: &[Key], op: Option<OpType>) {
+        let mut node = &mut self.root;
+        for key in keys {
+            node = node.children.entry(*key).or_default();
+        }
+        node.op = op;
+    }
+
+    pub fn lookup(&self, keys: &[Key]) -> Lookup<'_> {
+        let mut node = &self.root;
+        for key in keys {
+            match node.children.get(key) {
+                Some(child) => node = child,
+                None => return Lookup::Unbound,
+            }
+        }
+        match (node.op.as_ref(), node.children.is_empty()) {
+            (None, true) => Lookup::Unbound,
+            (Some(op), true) => Lookup::Bound(op),
+            (op, false) => Lookup::Prefix(op),
+        }
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{collections::BTreeSet, path::PathBuf};
 
 use crate::cursor::Cursor;
 
@@ -10,37 +10,107 @@ impl Default for Lines {
     }
 }
 
+/// The window of listing rows that fits on screen.
+pub struct Viewport {
+    pub top: usize,
+    pub height: usize,
+    pub scrolloff: usize,
+}
+
+impl Viewport {
+    pub fn new(scrolloff: usize) -> Self {
+        Self {
+            top: 0,
+            height: 1,
+            scrolloff,
+        }
+    }
+
+    /// Scrolls so that `pos` stays visible with `scrolloff` rows around it.
+    pub fn scroll(&mut self, pos: usize, len: usize) {
+        let so = self.scrolloff.min(self.height.saturating_sub(1) / 2);
+        if pos < self.top + so {
+            self.top = pos.saturating_sub(so);
+        } else if pos + so >= self.top + self.height {
+            self.top = pos + so + 1 - self.height;
+        }
+        self.top = self.top.min(len.saturating_sub(self.height));
+    }
+
+    pub fn screen_top(&self) -> usize {
+        match self.top {
+            0 => 0,
+            top => top + self.scrolloff.min(self.height.saturating_sub(1) / 2),
+        }
+    }
+
+    pub fn screen_middle(&self, len: usize) -> usize {
+        let rows = self.height.min(len.saturating_sub(self.top));
+        self.top + rows.saturating_sub(1) / 2
+    }
+
+    pub fn screen_bottom(&self, len: usize) -> usize {
+        if self.top + self.height >= len {
+            len.saturating_sub(1)
+        } else {
+            let so = self.scrolloff.min(self.height.saturating_sub(1) / 2);
+            self.top + self.height - 1 - so
+        }
+    }
+}
+
 impl Lines {
     pub fn new() -> Self {
         Self {}
     }
 
-    pub(crate) fn format(&self, cursor: &mut dyn Cursor) -> anyhow::Result<Vec<String>> {
-        let rows = match cursor.current_siblings()? {
-            Some(content) => content,
-            None => vec![PathBuf::from("   ../")],
-        };
-
+    /// Formats the header and the rows of the listing that fit in `viewport`.
+    /// The selected row starts with `>` and marked rows with `*`.
+    pub(crate) fn format(
+        &self,
+        cursor: &mut dyn Cursor,
+        viewport: &mut Viewport,
+        marks: &BTreeSet<PathBuf>,
+    ) -> anyhow::Result<Vec<String>> {
         let mut lines = Vec::new();
-        lines.push(format!("{}", cursor.current_dir().display()));
+        match cursor.filter() {
+            Some(filter) => {
+                lines.push(format!("{} [g/{}]", cursor.current_dir().display(), filter))
+            }
+            None => lines.push(format!("{}", cursor.current_dir().display())),
+        }
         lines.push(String::from(""));
 
-        for path in rows {
+        let len = cursor.current_len()?;
+        let pos = cursor.pos()? as usize;
+        viewport.scroll(pos, len.max(1));
+
+        // only the rows on screen are copied out of the listing
+        let rows = match len {
+            0 => vec![PathBuf::from("   ../")],
+            _ => cursor.current_window(viewport.top..viewport.top + viewport.height)?,
+        };
+        for (i, path) in (viewport.top..).zip(rows.iter()) {
             let s = path
                 .file_name()
                 .unwrap_or_default()
                 .to_str()
-                .unwrap_or_default();
+                .unwrap_or_default()
+                .trim_start();
             let s = match path.is_dir() || path.to_str().unwrap_or("").ends_with('/') {
-                true => format!("   {s}/"),
-                false => format!("   {s}"),
+                true => format!("{s}/"),
+                false => s.to_string(),
             };
-            lines.push(s);
+            let selected = if i == pos { '>' } else { ' ' };
+            let marked = if marks.contains(path) { '*' } else { ' ' };
+            match cursor.annotation(path) {
+                Some(annotation) => {
+                    lines.push(format!(" {}{}{}  ({})", selected, marked, s, annotation))
+                }
+                None => lines.push(format!(" {}{}{}", selected, marked, s)),
+            }
         }
 
-        let index = (cursor.pos()? + 2) as usize;
-        lines[index] = format!(" > {}", lines[index].trim_start());
-
         Ok(lines)
     }
 }
@@ -1,11 +1,28 @@
 use std::io;
 
+pub mod archive_cursor;
+pub mod archive_tree;
+pub mod command;
+pub mod compress;
+pub mod compression;
+pub mod config;
 pub mod cursor;
+pub mod date;
 pub mod engine;
 pub mod explorer;
+pub mod extract;
 pub mod file_cursor;
+pub mod fileops;
+pub mod keymap;
 pub mod lines;
-pub mod tar_cursor;
+pub mod matcher;
+pub mod prompt;
+pub mod rename;
+pub mod tar_members;
+pub mod trash;
+pub mod trash_cursor;
+pub mod watcher;
+pub mod zip_members;
 
 fn main() -> anyhow::Result<()> {
     let mut stdout = io::stdout();
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+
+/// Matches file names against a search or filter term.
+///
+/// The prefix of the term picks the flavour: `re:` for a regex, `glob:` for
+/// a glob such as `*.rs`, anything else is a literal substring. Matching is
+/// smart-case: case-sensitive only when the pattern has an uppercase letter.
+pub enum Matcher {
+    Literal(String, bool),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn new(term: &str) -> anyhow::Result<Matcher> {
+        if let Some(pattern) = term.strip_prefix("re:") {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(!smart_case(pattern))
+                .build()?;
+            Ok(Matcher::Regex(regex))
+        } else if let Some(pattern) = term.strip_prefix("glob:") {
+            let glob = GlobBuilder::new(pattern)
+                .case_insensitive(!smart_case(pattern))
+                .literal_separator(true)
+                .build()?;
+            Ok(Matcher::Glob(glob.compile_matcher()))
+        } else if smart_case(term) {
+            Ok(Matcher::Literal(term.to_string(), true))
+        } else {
+            Ok(Matcher::Literal(term.to_lowercase(), false))
+        }
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match self {
+            Matcher::Literal(pattern, true) => name.contains(pattern.as_str()),
+            Matcher::Literal(pattern, false) => name.to_lowercase().contains(pattern.as_str()),
+            Matcher::Glob(glob) => glob.is_match(name.as_ref()),
+            Matcher::Regex(regex) => regex.is_match(&name),
+        }
+    }
+}
+
+fn smart_case(pattern: &str) -> bool {
+    pattern.chars().any(|c| c.is_uppercase())
+}
@@ -0,0 +1,96 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyModifiers},
+    queue, style,
+    terminal::{self, ClearType},
+};
+
+/// Reads a line of input on the bottom row, starting out with `initial`.
+/// Returns `None` when the prompt is cancelled with Esc or Ctrl-c.
+pub fn input(question: &str, initial: &str) -> anyhow::Result<Option<String>> {
+    input_at(question, initial, initial.chars().count())
+}
+
+/// Like `input`, with the cursor starting at character `pos` of `initial`.
+/// The cursor moves with the arrow keys, Home and End or Ctrl-a and Ctrl-e.
+pub fn input_at(question: &str, initial: &str, pos: usize) -> anyhow::Result<Option<String>> {
+    let mut line: Vec<char> = initial.chars().collect();
+    let mut pos = pos.min(line.len());
+    loop {
+        let text = format!("{}{}", question, line.iter().collect::<String>());
+        draw(&text, Some(question.chars().count() + pos))?;
+        if let Event::Key(ke) = event::read()? {
+            let ctrl = ke.modifiers.contains(KeyModifiers::CONTROL);
+            match ke.code {
+                KeyCode::Char('c') if ctrl => return Ok(None),
+                KeyCode::Char('u') if ctrl => {
+                    line.drain(..pos);
+                    pos = 0;
+                }
+                KeyCode::Char('a') if ctrl => pos = 0,
+                KeyCode::Char('e') if ctrl => pos = line.len(),
+                KeyCode::Char(c) => {
+                    line.insert(pos, c);
+                    pos += 1;
+                }
+                KeyCode::Backspace if pos > 0 => {
+                    pos -= 1;
+                    line.remove(pos);
+                }
+                KeyCode::Delete if pos < line.len() => {
+                    line.remove(pos);
+                }
+                KeyCode::Left => pos = pos.saturating_sub(1),
+                KeyCode::Right => pos = (pos + 1).min(line.len()),
+                KeyCode::Home => pos = 0,
+                KeyCode::End => pos = line.len(),
+                KeyCode::Enter => return Ok(Some(line.into_iter().collect())),
+                KeyCode::Esc => return Ok(None),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Asks `question` and waits for one of `keys`. Any other key counts as
+/// `None`, so only the listed keys confirm anything.
+pub fn choose(question: &str, keys: &str) -> anyhow::Result<Option<char>> {
+    draw(question, None)?;
+    loop {
+        if let Event::Key(ke) = event::read()? {
+            return Ok(match ke.code {
+                KeyCode::Char(c) if keys.contains(c) => Some(c),
+                _ => None,
+            });
+        }
+    }
+}
+
+/// Shows `text` on the bottom row while something is in progress.
+pub fn show(text: &str) -> anyhow::Result<()> {
+    draw(text, None)
+}
+
+/// Draws `text` on the bottom row, with the terminal cursor at `cursor`
+/// when given.
+fn draw(text: &str, cursor: Option<usize>) -> anyhow::Result<()> {
+    let mut w = io::stdout();
+    let (_, term_height) = terminal::size()?;
+    queue!(
+        w,
+        crossterm::cursor::MoveTo(0, term_height.saturating_sub(1)),
+        terminal::Clear(ClearType::CurrentLine),
+        style::Print(text)
+    )?;
+    match cursor {
+        Some(column) => queue!(
+            w,
+            crossterm::cursor::MoveToColumn(column as u16),
+            crossterm::cursor::Show
+        )?,
+        None => queue!(w, crossterm::cursor::Hide)?,
+    }
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::fileops;
+
+/// The line `path` is listed as in the file edited for a bulk rename: its
+/// name when it is in `dir`, its full path otherwise.
+pub fn line(path: &Path, dir: &Path) -> String {
+    match path.parent() == Some(dir) {
+        true => path.file_name().unwrap_or_default().to_string_lossy(),
+        false => path.to_string_lossy(),
+    }
+    .to_string()
+}
+
+/// Pairs each of `paths` with the name on the same line of `edited`, which
+/// started out as `lines`. Relative names are in `dir`. Only changed lines
+/// are returned, and nothing is returned unless all of them can be renamed
+/// together: no name may be empty or taken twice, or taken on disk by
+/// something that isn't renamed away as well.
+pub fn plan(
+    paths: &[PathBuf],
+    lines: &[String],
+    edited: &str,
+    dir: &Path,
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let edited: Vec<&str> = edited.lines().collect();
+    if edited.len() != lines.len() {
+        anyhow::bail!("expected {} lines, got {}", lines.len(), edited.len());
+    }
+
+    // a path listed twice would be moved twice
+    if paths.iter().collect::<HashSet<_>>().len() != paths.len() {
+        anyhow::bail!("a path is listed twice");
+    }
+
+    let mut renames = Vec::new();
+    for ((path, line), edited) in paths.iter().zip(lines).zip(edited) {
+        if edited.trim().is_empty() {
+            anyhow::bail!("empty name for {}", path.display());
+        }
+        if edited != line {
+            renames.push((path.clone(), dir.join(edited)));
+        }
+    }
+
+    let sources: HashSet<&Path> = renames.iter().map(|(from, _)| from.as_path()).collect();
+    let mut targets = HashSet::new();
+    for (from, to) in &renames {
+        if !targets.insert(to.as_path()) {
+            anyhow::bail!("{} is named twice", to.display());
+        }
+        if to.starts_with(from) {
+            anyhow::bail!("can't rename {} into itself", from.display());
+        }
+        if let Some(source) = sources.iter().find(|s| from.starts_with(s) && from != *s) {
+            anyhow::bail!(
+                "{} is inside {}, which is renamed too",
+                from.display(),
+                source.display()
+            );
+        }
+        if to.symlink_metadata().is_ok() && !sources.contains(to.as_path()) {
+            anyhow::bail!("{} exists", to.display());
+        }
+        let parent = to.parent().unwrap_or(Path::new("/"));
+        if !parent.is_dir() || sources.iter().any(|s| parent.starts_with(s)) {
+            anyhow::bail!("no directory {}", parent.display());
+        }
+    }
+    Ok(renames)
+}
+
+/// Carries out the renames checked by `plan` and returns how many are done.
+/// A rename waits until its target has been moved away. When all remaining
+/// targets are still taken, the renames form cycles like a→b, b→a, which are
+/// broken by first moving one source to a temporary name.
+pub fn apply(mut renames: Vec<(PathBuf, PathBuf)>) -> (usize, anyhow::Result<()>) {
+    let mut done = 0;
+    while !renames.is_empty() {
+        let free = renames
+            .iter()
+            .position(|(_, to)| !renames.iter().any(|(from, _)| from == to));
+        let result = match free {
+            Some(i) => {
+                let (from, to) = renames.remove(i);
+                fileops::move_path(&from, &to).map(|_| done += 1)
+            }
+            None => {
+                let (from, _) = &mut renames[0];
+                let mut name = from.file_name().unwrap_or_default().to_os_string();
+                name.push(".rename");
+                let temp = fileops::free_name(&from.with_file_name(name));
+                fileops::move_path(from, &temp).map(|_| *from = temp)
+            }
+        };
+        if let Err(e) = result {
+            return (done, Err(e));
+        }
+    }
+    (done, Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A directory with an empty file for each of `names`, and their paths.
+    fn setup(names: &[&str]) -> (tempfile::TempDir, Vec<PathBuf>) {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = names.iter().map(|n| dir.path().join(n)).collect();
+        for (path, name) in paths.iter().zip(names) {
+            fs::write(path, name).unwrap();
+        }
+        (dir, paths)
+    }
+
+    fn lines(paths: &[PathBuf], dir: &Path) -> Vec<String> {
+        paths.iter().map(|p| line(p, dir)).collect()
+    }
+
+    fn error(result: anyhow::Result<Vec<(PathBuf, PathBuf)>>) -> String {
+        format!("{:#}", result.unwrap_err())
+    }
+
+    #[test]
+    fn line_is_name_in_dir_and_path_elsewhere() {
+        let dir = Path::new("/a");
+        assert_eq!(line(Path::new("/a/b.txt"), dir), "b.txt");
+        assert_eq!(line(Path::new("/c/d.txt"), dir), "/c/d.txt");
+    }
+
+    #[test]
+    fn plan_returns_changed_lines_only() {
+        let (dir, paths) = setup(&["a", "b"]);
+        let lines = lines(&paths, dir.path());
+
+        let renames = plan(&paths, &lines, "a\nc\n", dir.path()).unwrap();
+
+        assert_eq!(renames, vec![(paths[1].clone(), dir.path().join("c"))]);
+    }
+
+    #[test]
+    fn plan_rejects_wrong_line_count() {
+        let (dir, paths) = setup(&["a", "b"]);
+        let lines = lines(&paths, dir.path());
+        assert_eq!(
+            error(plan(&paths, &lines, "a\n", dir.path())),
+            "expected 2 lines, got 1"
+        );
+    }
+
+    #[test]
+    fn plan_rejects_empty_names() {
+        let (dir, paths) = setup(&["a"]);
+        let lines = lines(&paths, dir.path());
+        assert!(error(plan(&paths, &lines, " \n", dir.path())).starts_with("empty name"));
+    }
+
+    #[test]
+    fn plan_rejects_duplicates() {
+        let (dir, paths) = setup(&["a", "b"]);
+        let lines = lines(&paths, dir.path());
+        assert!(error(plan(&paths, &lines, "c\nc\n", dir.path())).ends_with("is named twice"));
+
+        let twice = vec![paths[0].clone(), paths[0].clone()];
+        let lines = vec![String::from("a"), String::from("a")];
+        assert_eq!(
+            error(plan(&twice, &lines, "c\nd\n", dir.path())),
+            "a path is listed twice"
+        );
+    }
+
+    #[test]
+    fn plan_rejects_existing_targets() {
+        let (dir, paths) = setup(&["a", "b"]);
+        let lines = lines(&paths[..1], dir.path());
+        assert!(error(plan(&paths[..1], &lines, "b\n", dir.path())).ends_with("b exists"));
+    }
+
+    #[test]
+    fn plan_rejects_renaming_into_itself() {
+        let (dir, _) = setup(&[]);
+        let paths = vec![dir.path().join("d")];
+        fs::create_dir(&paths[0]).unwrap();
+        let lines = lines(&paths, dir.path());
+        assert!(error(plan(&paths, &lines, "d/e\n", dir.path())).contains("into itself"));
+    }
+
+    #[test]
+    fn plan_accepts_targets_renamed_away() {
+        let (dir, paths) = setup(&["a", "b"]);
+        let lines = lines(&paths, dir.path());
+
+        let renames = plan(&paths, &lines, "b\na\n", dir.path()).unwrap();
+
+        assert_eq!(
+            renames,
+            vec![
+                (paths[0].clone(), paths[1].clone()),
+                (paths[1].clone(), paths[0].clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_swaps_names() {
+        let (dir, paths) = setup(&["a", "b"]);
+        let lines = lines(&paths, dir.path());
+        let renames = plan(&paths, &lines, "b\na\n", dir.path()).unwrap();
+
+        let (done, result) = apply(renames);
+
+        result.unwrap();
+        assert_eq!(done, 2);
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), "b");
+        assert_eq!(fs::read_to_string(&paths[1]).unwrap(), "a");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn apply_orders_chains() {
+        let (dir, paths) = setup(&["a", "b"]);
+        let lines = lines(&paths, dir.path());
+        let renames = plan(&paths, &lines, "b\nc\n", dir.path()).unwrap();
+
+        let (done, result) = apply(renames);
+
+        result.unwrap();
+        assert_eq!(done, 2);
+        assert!(!paths[0].exists());
+        assert_eq!(fs::read_to_string(&paths[1]).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.path().join("c")).unwrap(), "b");
+    }
+}
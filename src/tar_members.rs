@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use tar::{Archive, Entries, EntryType};
+
+use crate::archive_cursor::Members;
+use crate::archive_tree::{normalise, ArchiveTree};
+use crate::compression::{self, Compression};
+use crate::cursor::{Entry, Link};
+
+/// Where the data of an entry lives in the (decompressed) archive stream.
+struct Span {
+    data: u64,
+    size: u64,
+}
+
+/// Copies everything read from a decompressing reader to a spill file, so
+/// the listing pass leaves a seekable copy of the archive stream behind.
+struct Tee<R> {
+    reader: R,
+    spill: File,
+}
+
+impl<R: Read> Read for Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.spill.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+/// The members of a tar archive, read from where the listing found them.
+/// Entry data is read straight from its offset in `source`, which is the
+/// archive itself when it is uncompressed. Compressed streams can't be
+/// seeked, so they are decompressed once while listing and the stream is
+/// spilled to an anonymous temp file as it goes, which takes as much space in
+/// the temp directory as the uncompressed archive.
+pub struct TarMembers {
+    root: PathBuf,
+    file: PathBuf,
+    spans: HashMap<PathBuf, Span>,
+    source: File,
+}
+
+impl TarMembers {
+    /// Lists the archive read from `file` below the virtual directory `root`.
+    /// The two differ for an archive nested in another archive, which is
+    /// browsed under its own path but read from a temp file.
+    pub fn open(root: &Path, file: &Path) -> Result<(ArchiveTree, TarMembers)> {
+        let (tree, spans, source) = match Compression::detect(file)? {
+            // uncompressed archives skip over entry data with seeks
+            Compression::None => {
+                let mut archive = Archive::new(File::open(file)?);
+                let (tree, spans) = Self::populate(root, archive.entries_with_seek()?)?;
+                (tree, spans, archive.into_inner())
+            }
+            compression => {
+                let mut archive = Archive::new(Tee {
+                    reader: compression.decoder(File::open(file)?)?,
+                    spill: tempfile::tempfile()?,
+                });
+                let (tree, spans) = Self::populate(root, archive.entries()?)?;
+                (tree, spans, archive.into_inner().spill)
+            }
+        };
+        let members = TarMembers {
+            root: root.to_path_buf(),
+            file: file.to_path_buf(),
+            spans,
+            source,
+        };
+        Ok((tree, members))
+    }
+
+    /// Adds every entry to the tree and records where its data starts.
+    fn populate<R: Read>(
+        root: &Path,
+        entries: Entries<R>,
+    ) -> Result<(ArchiveTree, HashMap<PathBuf, Span>)> {
+        let mut tree = ArchiveTree::new();
+        let mut spans = HashMap::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path()?.to_path_buf();
+            let header = entry.header();
+            if header.entry_type().is_pax_global_extensions() {
+                continue;
+            }
+
+            let mtime = header.mtime().unwrap_or(0);
+            let link = match (header.entry_type(), entry.link_name()?) {
+                (EntryType::Symlink, Some(target)) => Some(Link::Symlink(target.to_path_buf())),
+                // hard links name another member
+                (EntryType::Link, Some(target)) => {
+                    let mut path = root.to_path_buf();
+                    path.extend(normalise(&target));
+                    Some(Link::Hard(path))
+                }
+                _ => None,
+            };
+            let file = tree.insert(
+                root,
+                &path,
+                Entry {
+                    path: PathBuf::new(),
+                    size: entry.size(),
+                    mtime: UNIX_EPOCH + Duration::from_secs(mtime),
+                    mode: header.mode().unwrap_or(0),
+                    is_dir: header.entry_type().is_dir()
+                        || path.to_str().unwrap_or("").ends_with('/'),
+                    link,
+                },
+            );
+
+            // sparse entries aren't stored contiguously, they are read by
+            // scanning the archive instead
+            if let Some(file) = file.filter(|_| !header.entry_type().is_gnu_sparse()) {
+                spans.insert(
+                    file,
+                    Span {
+                        data: entry.raw_file_position(),
+                        size: entry.size(),
+                    },
+                );
+            }
+        }
+        Ok((tree, spans))
+    }
+}
+
+impl Members for TarMembers {
+    fn read(&mut self, path: &Path) -> Result<Vec<u8>> {
+        if let Some(span) = self.spans.get(path) {
+            let mut buffer = vec![0; span.size as usize];
+            self.source.seek(SeekFrom::Start(span.data))?;
+            self.source.read_exact(&mut buffer)?;
+            return Ok(buffer);
+        }
+
+        let relative_path = path.strip_prefix(&self.root)?;
+        let mut archive = Archive::new(compression::open(&self.file)?);
+        let mut entry = archive
+            .entries()?
+            .filter_map(|e| e.ok())
+            .find(|e| {
+                e.path()
+                    .ok()
+                    .map(|p| normalise(&p) == normalise(relative_path))
+                    .unwrap_or(false)
+            })
+            .ok_or_else(|| anyhow::anyhow!("File not found in archive"))?;
+
+        let mut buffer = Vec::new();
+        entry.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+
+use crate::date;
+use crate::fileops;
+
+/// An entry of the trash and the place it was deleted from.
+pub struct TrashItem {
+    pub path: PathBuf,
+    pub original: PathBuf,
+    pub deleted: String,
+}
+
+/// The home trash as in the freedesktop.org Trash spec,
+/// `$XDG_DATA_HOME/Trash` or `~/.local/share/Trash`. The environment is read
+/// on every call rather than once at startup.
+pub fn trash_dir() -> anyhow::Result<PathBuf> {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(
+            std::env::var_os("HOME").ok_or_else(|| anyhow::anyhow!("$HOME is not set"))?,
+        )
+        .join(".local/share"),
+    };
+    Ok(base.join("Trash"))
+}
+
+/// The directory trashed entries are kept in, created when missing.
+pub fn files_dir() -> anyhow::Result<PathBuf> {
+    let files = trash_dir()?.join("files");
+    fs::create_dir_all(&files).with_context(|| format!("can't create {}", files.display()))?;
+    Ok(files)
+}
+
+fn info_dir() -> anyhow::Result<PathBuf> {
+    let info = trash_dir()?.join("info");
+    fs::create_dir_all(&info).with_context(|| format!("can't create {}", info.display()))?;
+    Ok(info)
+}
+
+/// The `.trashinfo` file describing the trashed entry `path`.
+fn info_file(path: &Path) -> anyhow::Result<PathBuf> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".trashinfo");
+    Ok(info_dir()?.join(name))
+}
+
+/// Moves `path` to the trash and returns where it ended up. The info file
+/// is created first and exclusively, which reserves the name in the trash.
+/// Only the home trash is used, entries on other filesystems are copied
+/// there.
+pub fn trash(path: &Path) -> anyhow::Result<PathBuf> {
+    let files = files_dir()?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("can't trash {}", path.display()))?;
+    let content = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode(path),
+        deletion_date(SystemTime::now())
+    );
+
+    for n in 0.. {
+        let target = match n {
+            0 => files.join(name),
+            n => fileops::numbered(&files.join(name), n),
+        };
+        if target.symlink_metadata().is_ok() {
+            continue;
+        }
+        let info = info_file(&target)?;
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&info) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("can't create {}", info.display())),
+        };
+        let moved = file
+            .write_all(content.as_bytes())
+            .map_err(anyhow::Error::from)
+            .and_then(|_| fileops::move_path(path, &target));
+        if let Err(e) = moved {
+            let _ = fs::remove_file(&info);
+            return Err(e);
+        }
+        return Ok(target);
+    }
+    unreachable!()
+}
+
+/// Everything in the trash that has an info file.
+pub fn items() -> anyhow::Result<Vec<TrashItem>> {
+    let files = files_dir()?;
+    let mut items = Vec::new();
+    for entry in fs::read_dir(info_dir()?)? {
+        let info = entry?.path();
+        if info.extension().is_none_or(|e| e != "trashinfo") {
+            continue;
+        }
+        let path = files.join(info.file_stem().unwrap_or_default());
+        if path.symlink_metadata().is_err() {
+            continue;
+        }
+        let content = fs::read_to_string(&info)?;
+        let value = |key: &str| {
+            content
+                .lines()
+                .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+                .unwrap_or_default()
+        };
+        items.push(TrashItem {
+            path,
+            original: decode(value("Path")),
+            deleted: value("DeletionDate").to_string(),
+        });
+    }
+    Ok(items)
+}
+
+/// Moves the trashed entry `path` back to where it was deleted from, or next
+/// to it as `name (1).ext` when that place is taken again.
+pub fn restore(path: &Path) -> anyhow::Result<PathBuf> {
+    let original = items()?
+        .into_iter()
+        .find(|item| item.path == path)
+        .map(|item| item.original)
+        .ok_or_else(|| anyhow::anyhow!("{} has no trash info", path.display()))?;
+    let target = match original.symlink_metadata() {
+        Ok(_) => fileops::free_name(&original),
+        Err(_) => original,
+    };
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fileops::move_path(path, &target)?;
+    fs::remove_file(info_file(path)?)?;
+    Ok(target)
+}
+
+/// Deletes the trashed entry `path` for good, along with its info file.
+/// Entries below a trashed directory have no info file of their own.
+pub fn purge(path: &Path) -> anyhow::Result<()> {
+    fileops::remove(path)?;
+    if path.parent() == Some(files_dir()?.as_path()) {
+        let info = info_file(path)?;
+        if info.exists() {
+            fs::remove_file(info)?;
+        }
+    }
+    Ok(())
+}
+
+/// The deletion date in local time, as the spec asks, without a zone.
+fn deletion_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let secs = secs + date::local_offset(secs);
+    let (year, month, day) = date::civil_from_days(secs.div_euclid(86400));
+    let tod = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        tod / 3600,
+        tod % 3600 / 60,
+        tod % 60
+    )
+}
+
+/// Percent-encodes a path the way URIs are, keeping `/`.
+fn encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(b as char)
+            }
+            b => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+fn decode(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(std::ffi::OsString::from_vec(decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// The trash is found through the environment, which all tests share.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Points `$XDG_DATA_HOME` at a fresh directory and returns it with a
+    /// directory to trash files from. The time zone is fixed at UTC+2.
+    fn setup() -> (
+        MutexGuard<'static, ()>,
+        tempfile::TempDir,
+        tempfile::TempDir,
+    ) {
+        let guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let data = tempfile::tempdir().unwrap();
+        std::env::set_var("XDG_DATA_HOME", data.path());
+        std::env::set_var("TZ", "XYZ-2");
+        (guard, data, tempfile::tempdir().unwrap())
+    }
+
+    fn create(path: &Path, content: &str) {
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn trash_writes_file_and_info() {
+        let (_guard, data, dir) = setup();
+        let path = dir.path().join("a b.txt");
+        create(&path, "a");
+
+        let trashed = trash(&path).unwrap();
+
+        let files = data.path().join("Trash/files");
+        assert_eq!(trashed, files.join("a b.txt"));
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(&trashed).unwrap(), "a");
+        let info = fs::read_to_string(data.path().join("Trash/info/a b.txt.trashinfo")).unwrap();
+        let lines: Vec<&str> = info.lines().collect();
+        assert_eq!(lines[0], "[Trash Info]");
+        assert_eq!(lines[1], format!("Path={}/a%20b.txt", dir.path().display()));
+        let date = lines[2].strip_prefix("DeletionDate=").unwrap();
+        assert_eq!(date.len(), "2024-01-01T00:00:00".len());
+        assert_eq!(&date[10..11], "T");
+    }
+
+    #[test]
+    fn deletion_date_is_local_time() {
+        let (_guard, _data, _dir) = setup();
+        assert_eq!(deletion_date(UNIX_EPOCH), "1970-01-01T02:00:00");
+    }
+
+    #[test]
+    fn trash_numbers_colliding_names() {
+        let (_guard, data, dir) = setup();
+        for sub in ["one", "two"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+            create(&dir.path().join(sub).join("a.txt"), sub);
+        }
+
+        let first = trash(&dir.path().join("one/a.txt")).unwrap();
+        let second = trash(&dir.path().join("two/a.txt")).unwrap();
+
+        let trash = data.path().join("Trash");
+        assert_eq!(first, trash.join("files/a.txt"));
+        assert_eq!(second, trash.join("files/a (1).txt"));
+        assert_eq!(fs::read_to_string(&second).unwrap(), "two");
+        assert!(trash.join("info/a (1).txt.trashinfo").exists());
+    }
+
+    #[test]
+    fn items_lists_trashed_entries() {
+        let (_guard, _data, dir) = setup();
+        let file = dir.path().join("file");
+        let sub = dir.path().join("sub");
+        create(&file, "");
+        fs::create_dir(&sub).unwrap();
+        let trashed = [trash(&file).unwrap(), trash(&sub).unwrap()];
+
+        let mut items = items().unwrap();
+        items.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(items.len(), 2);
+        for (item, (path, original)) in items.iter().zip(trashed.iter().zip([&file, &sub])) {
+            assert_eq!(&item.path, path);
+            assert_eq!(&item.original, original);
+            assert!(!item.deleted.is_empty());
+        }
+    }
+
+    #[test]
+    fn restore_moves_back() {
+        let (_guard, data, dir) = setup();
+        let path = dir.path().join("gone/a.txt");
+        fs::create_dir(dir.path().join("gone")).unwrap();
+        create(&path, "a");
+        let trashed = trash(&path).unwrap();
+        fs::remove_dir(dir.path().join("gone")).unwrap();
+
+        assert_eq!(restore(&trashed).unwrap(), path);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a");
+        assert!(!trashed.exists());
+        assert!(!data.path().join("Trash/info/a.txt.trashinfo").exists());
+        assert!(items().unwrap().is_empty());
+    }
+
+    #[test]
+    fn restore_onto_existing_path() {
+        let (_guard, _data, dir) = setup();
+        let path = dir.path().join("a.txt");
+        create(&path, "old");
+        let trashed = trash(&path).unwrap();
+        create(&path, "new");
+
+        let restored = restore(&trashed).unwrap();
+
+        assert_eq!(restored, dir.path().join("a (1).txt"));
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "old");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    }
+
+    #[test]
+    fn purge_removes_entry_and_info() {
+        let (_guard, data, dir) = setup();
+        let path = dir.path().join("sub");
+        fs::create_dir(&path).unwrap();
+        create(&path.join("inner"), "");
+        let trashed = trash(&path).unwrap();
+
+        purge(&trashed.join("inner")).unwrap();
+        assert!(data.path().join("Trash/info/sub.trashinfo").exists());
+        purge(&trashed).unwrap();
+
+        assert!(!trashed.exists());
+        assert!(!data.path().join("Trash/info/sub.trashinfo").exists());
+        assert!(items().unwrap().is_empty());
+    }
+}
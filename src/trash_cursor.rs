@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+
+use crate::cursor::{Cursor, Entry, Sort};
+use crate::file_cursor::FileCursor;
+use crate::trash::{self, TrashItem};
+
+/// Browses the trash. Trashed entries are ordinary files, so listing and
+/// moving around is left to a `FileCursor` rooted at the trash, which can't
+/// be left with `h`. Top level entries are annotated with where they were
+/// deleted from.
+pub struct TrashCursor {
+    files: FileCursor,
+    root: PathBuf,
+    items: HashMap<PathBuf, TrashItem>,
+}
+
+impl TrashCursor {
+    pub fn new() -> Self {
+        Self {
+            files: FileCursor::new(),
+            root: PathBuf::new(),
+            items: HashMap::new(),
+        }
+    }
+
+    fn load_items(&mut self) -> Result<()> {
+        self.items = trash::items()?
+            .into_iter()
+            .map(|item| (item.path.clone(), item))
+            .collect();
+        Ok(())
+    }
+}
+
+impl Default for TrashCursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cursor for TrashCursor {
+    fn init(&mut self, cwd: &Path) -> Result<()> {
+        self.root = cwd.to_path_buf();
+        self.files.init(cwd)?;
+        self.load_items()
+    }
+
+    fn read_file_content(&mut self, path: &Path) -> Result<Vec<u8>> {
+        self.files.read_file_content(path)
+    }
+
+    fn move_down(&mut self, n: i32) -> Result<()> {
+        self.files.move_down(n)
+    }
+
+    fn move_up(&mut self, n: i32) -> Result<()> {
+        self.files.move_up(n)
+    }
+
+    fn move_in(&mut self) -> Result<()> {
+        self.files.move_in()
+    }
+
+    fn move_out(&mut self) -> Result<()> {
+        if self.current_dir() != self.root {
+            self.files.move_out()?;
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, dir: &Path) -> Result<()> {
+        if !dir.starts_with(&self.root) {
+            anyhow::bail!("{} is outside the trash", dir.display());
+        }
+        self.files.move_to(dir)
+    }
+
+    fn move_bottom(&mut self) -> Result<()> {
+        self.files.move_bottom()
+    }
+
+    fn move_top(&mut self) -> Result<()> {
+        self.files.move_top()
+    }
+
+    fn toggle_hidden_files(&mut self) -> Result<()> {
+        self.files.toggle_hidden_files()
+    }
+
+    fn hides_hidden_files(&self) -> bool {
+        self.files.hides_hidden_files()
+    }
+
+    fn toggle_case_sensitivity(&mut self) -> Result<()> {
+        self.files.toggle_case_sensitivity()
+    }
+
+    fn sort(&self) -> Sort {
+        self.files.sort()
+    }
+
+    fn sort_dir(&mut self) -> Result<()> {
+        self.files.sort_dir()
+    }
+
+    fn sort_name(&mut self) -> Result<()> {
+        self.files.sort_name()
+    }
+
+    fn sort_size(&mut self) -> Result<()> {
+        self.files.sort_size()
+    }
+
+    fn sort_time(&mut self) -> Result<()> {
+        self.files.sort_time()
+    }
+
+    fn search(&mut self, pattern: &str) -> Result<()> {
+        self.files.search(pattern)
+    }
+
+    fn matching_siblings(&mut self, pattern: &str) -> Result<Vec<PathBuf>> {
+        self.files.matching_siblings(pattern)
+    }
+
+    fn filter(&self) -> Option<String> {
+        self.files.filter()
+    }
+
+    fn set_filter(&mut self, pattern: &str) -> Result<()> {
+        self.files.set_filter(pattern)
+    }
+
+    fn select(&mut self, path: &Path) -> Result<()> {
+        self.files.select(path)
+    }
+
+    fn selected(&self) -> PathBuf {
+        self.files.selected()
+    }
+
+    fn current_dir(&self) -> PathBuf {
+        self.files.current_dir()
+    }
+
+    fn start_dir(&self) -> PathBuf {
+        self.root.clone()
+    }
+
+    fn parent(&self) -> PathBuf {
+        self.files.parent()
+    }
+
+    fn current_len(&mut self) -> Result<usize> {
+        self.files.current_len()
+    }
+
+    fn current_window(&mut self, range: Range<usize>) -> Result<Vec<PathBuf>> {
+        self.files.current_window(range)
+    }
+
+    fn siblings(&mut self, path: PathBuf) -> Result<Vec<PathBuf>> {
+        self.files.siblings(path)
+    }
+
+    fn entries(&mut self, dir: &Path) -> Result<Vec<Entry>> {
+        self.files.entries(dir)
+    }
+
+    fn annotation(&self, path: &Path) -> Option<String> {
+        self.items
+            .get(path)
+            .map(|item| format!("{}  {}", item.original.display(), item.deleted))
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        self.files.refresh()?;
+        self.load_items()
+    }
+
+    fn invalidate(&mut self, dir: &Path) -> Result<()> {
+        self.files.invalidate(dir)?;
+        if dir == self.root {
+            self.load_items()?;
+        }
+        Ok(())
+    }
+
+    fn pos(&mut self) -> Result<i32> {
+        self.files.pos()
+    }
+}
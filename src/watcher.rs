@@ -0,0 +1,86 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+
+/// Watches the directory being browsed so that the cached listing can be
+/// refreshed when entries are added, removed or renamed. The descriptor is
+/// `None` when the directory couldn't be watched.
+pub struct Watcher {
+    inotify: Inotify,
+    watched: Option<(PathBuf, Option<WatchDescriptor>)>,
+    buffer: Vec<u8>,
+}
+
+impl Watcher {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            inotify: Inotify::init()?,
+            watched: None,
+            buffer: vec![0; 4096],
+        })
+    }
+
+    /// Starts watching `dir` instead of the previously watched directory.
+    /// Returns `true` when the watch changed.
+    pub fn watch(&mut self, dir: &Path) -> anyhow::Result<bool> {
+        if self.watched.as_ref().is_some_and(|(p, _)| p == dir) {
+            return Ok(false);
+        }
+        if let Some((_, Some(wd))) = self.watched.take() {
+            // the watch is already gone if the directory was removed
+            let _ = self.inotify.watches().remove(wd);
+        }
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::ATTRIB;
+        let wd = self.inotify.watches().add(dir, mask).ok();
+        self.watched = Some((dir.to_path_buf(), wd));
+        Ok(true)
+    }
+
+    /// The directory with a live watch, if any.
+    pub fn watched(&self) -> Option<&Path> {
+        match &self.watched {
+            Some((dir, Some(_))) => Some(dir),
+            _ => None,
+        }
+    }
+
+    /// Drains pending events without blocking and returns the watched
+    /// directory if anything in it changed.
+    pub fn changed(&mut self) -> anyhow::Result<Option<PathBuf>> {
+        let mut changed = false;
+        loop {
+            match self.inotify.read_events(&mut self.buffer) {
+                Ok(events) => {
+                    let mut empty = true;
+                    for event in events {
+                        empty = false;
+                        if self
+                            .watched
+                            .as_ref()
+                            .is_some_and(|(_, wd)| wd.as_ref() == Some(&event.wd))
+                        {
+                            changed = true;
+                        }
+                    }
+                    if empty {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match changed {
+            true => Ok(self.watched.as_ref().map(|(p, _)| p.clone())),
+            false => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use zip::ZipArchive;
+
+use crate::archive_cursor::Members;
+use crate::archive_tree::ArchiveTree;
+use crate::cursor::{Entry, Link};
+use crate::date;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFDIR: u32 = 0o040000;
+
+/// The members of a zip archive, each decompressed on its own.
+pub struct ZipMembers {
+    archive: ZipArchive<File>,
+    // member names are normalised, so entries are looked up by position
+    index: HashMap<PathBuf, usize>,
+}
+
+impl ZipMembers {
+    /// Lists the archive read from `file` below the virtual directory `root`.
+    /// The central directory lists every entry, nothing is decompressed
+    /// except the targets of symlinks.
+    pub fn open(root: &Path, file: &Path) -> Result<(ArchiveTree, ZipMembers)> {
+        let mut archive = ZipArchive::new(File::open(file)?)?;
+        let mut tree = ArchiveTree::new();
+        let mut index = HashMap::new();
+        for i in 0..archive.len() {
+            // symlinks are stored as files holding the target
+            let unix_mode = archive.by_index_raw(i)?.unix_mode();
+            let link = match unix_mode {
+                Some(mode) if mode & S_IFMT == S_IFLNK => {
+                    let mut target = String::new();
+                    archive.by_index(i)?.read_to_string(&mut target)?;
+                    Some(Link::Symlink(PathBuf::from(target)))
+                }
+                _ => None,
+            };
+            let file = archive.by_index_raw(i)?;
+            // archives made on Windows carry no mode, or one made up from
+            // attributes that may not even mark directories as such
+            let mode = match file.is_dir() {
+                true => unix_mode
+                    .filter(|mode| mode & S_IFMT == S_IFDIR)
+                    .unwrap_or(0o755),
+                false => unix_mode.unwrap_or(0o644),
+            };
+            let path = tree.insert(
+                root,
+                Path::new(file.name()),
+                Entry {
+                    path: PathBuf::new(),
+                    size: file.size(),
+                    mtime: system_time(file.last_modified()),
+                    mode,
+                    is_dir: file.is_dir(),
+                    link,
+                },
+            );
+            if let Some(path) = path.filter(|_| !file.is_dir()) {
+                index.insert(path, i);
+            }
+        }
+        Ok((tree, ZipMembers { archive, index }))
+    }
+}
+
+impl Members for ZipMembers {
+    fn read(&mut self, path: &Path) -> Result<Vec<u8>> {
+        let i = *self
+            .index
+            .get(path)
+            .ok_or_else(|| anyhow::anyhow!("File not found in archive"))?;
+        // only this entry is decompressed
+        let mut file = self.archive.by_index(i)?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// Converts a zip timestamp, which has no time zone, to a `SystemTime` taking
+/// it as local time like other tools do.
+fn system_time(dt: zip::DateTime) -> SystemTime {
+    let days = date::days_from_civil(dt.year() as i64, dt.month() as i64, dt.day() as i64);
+    let local =
+        days * 86400 + dt.hour() as i64 * 3600 + dt.minute() as i64 * 60 + dt.second() as i64;
+    // the offset at the UTC time itself, which differs across a DST change
+    let secs = local - date::local_offset(local - date::local_offset(local));
+    UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+}